@@ -47,10 +47,12 @@ pub fn setup(
         value_type: ShortTypePath::from_type_path::<bool>(),
     };
 
-    let mut track = Track::new(binding, 0.1, 2);
+    let grid = FrameGrid::new(0.1, 2);
 
-    track.add_keyframe(Keyframe::new(0, TrackValue::Number(0.0)));
-    track.add_keyframe(Keyframe::new(1, TrackValue::Number(1.0)));
+    let mut track = Track::new(binding, grid.duration());
+
+    track.add_keyframe(grid.keyframe(0, TrackValue::Number(0.0)));
+    track.add_keyframe(grid.keyframe(1, TrackValue::Number(1.0)));
 
     let mut entity_track = ComponentTrack::default();
 
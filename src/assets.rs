@@ -1,49 +1,435 @@
-use crate::{core::AnimationName, entity::EntityAnimation};
-use bevy::{
-    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
-    prelude::*,
-    reflect::TypePath,
-    utils::HashMap,
-};
-use thiserror::Error;
-
-use serde::{Deserialize, Serialize};
-
-#[derive(Default, Asset, TypePath, Clone, Deref, DerefMut, Deserialize, Serialize)]
-pub struct EntityAnimations(HashMap<AnimationName, EntityAnimation>);
-
-#[derive(Default)]
-pub struct EntityAnimationsLoader;
-
-// Possible errors that can be produced by [`CustomAssetLoader`]
-#[non_exhaustive]
-#[derive(Debug, Error)]
-pub enum EntityAnimationsLoaderError {
-    /// An [IO](std::io) Error
-    #[error("Could not load asset: {0}")]
-    Io(#[from] std::io::Error),
-    #[error("Could not load asset: {0}")]
-    Serde(#[from] serde_json::Error),
-}
-
-impl AssetLoader for EntityAnimationsLoader {
-    type Asset = EntityAnimations;
-    type Settings = ();
-    type Error = EntityAnimationsLoaderError;
-    async fn load<'a>(
-        &'a self,
-        reader: &'a mut Reader<'_>,
-        _settings: &'a (),
-        _load_context: &'a mut LoadContext<'_>,
-    ) -> Result<Self::Asset, Self::Error> {
-        let mut bytes = Vec::new();
-        reader.read_to_end(&mut bytes).await?;
-        let custom_asset = serde_json::from_slice::<EntityAnimations>(&bytes)?;
-
-        Ok(custom_asset)
-    }
-
-    fn extensions(&self) -> &[&str] {
-        &["entity_animations.json"]
-    }
-}
+use std::{
+    any::TypeId,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    core::{AnimationName, ShortTypePath},
+    entity::EntityAnimation,
+    track::{AnimateAssetFns, AnimateComponentFns, AnimateResourceFns},
+    value::{AnimateValueFns, TrackValue, ValueBinding},
+};
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    reflect::{Access, ParsedPath, TypeInfo, TypePath, TypeRegistry, TypeRegistryArc},
+    utils::HashMap,
+};
+use thiserror::Error;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Default, Asset, TypePath, Clone, Deref, DerefMut, Deserialize, Serialize)]
+pub struct EntityAnimations(HashMap<AnimationName, EntityAnimation>);
+
+///编译后的组件绑定
+pub struct CompiledComponent {
+    //组件、资源或资产的类型，用于检查字段路径
+    type_id: TypeId,
+    //无法解析的字段为 None
+    values: HashMap<String, Option<CompiledValue>>,
+}
+
+impl CompiledComponent {
+    ///字段类型是否无法插值，由 AnimateValue::DISCRETE 决定
+    pub fn is_discrete(&self, path: &str) -> bool {
+        self.values
+            .get(path)
+            .and_then(|value| value.as_ref())
+            .is_some_and(|value| value.fns.discrete)
+    }
+
+    ///写入失败过的字段也返回 None
+    pub fn value(&self, path: &str) -> Option<&CompiledValue> {
+        self.values
+            .get(path)
+            .and_then(|value| value.as_ref())
+            .filter(|value| !value.failed.load(Ordering::Relaxed))
+    }
+}
+
+///编译后的字段绑定
+pub struct CompiledValue {
+    pub path: ParsedPath,
+    pub fns: AnimateValueFns,
+    //第一次写入失败后不再写入，只报告一次
+    failed: AtomicBool,
+}
+
+impl CompiledValue {
+    pub fn set_failed(&self) {
+        self.failed.store(true, Ordering::Relaxed);
+    }
+}
+
+///解析了所有动画绑定的 EntityAnimations，在资源加载或类型注册变化时重新编译
+#[derive(Default)]
+pub struct CompiledEntityAnimations {
+    //无法解析的组件为 None
+    components: HashMap<ShortTypePath, Option<CompiledComponent>>,
+}
+
+impl CompiledEntityAnimations {
+    ///解析所有动画的绑定，无法解析的绑定只报告一次
+    pub fn compile(animations: &EntityAnimations, registry: &TypeRegistry) -> Self {
+        let mut compiled = CompiledEntityAnimations::default();
+
+        for (type_path, component_track) in animations
+            .values()
+            .flat_map(|animation| animation.component_tracks())
+        {
+            if !compiled.components.contains_key(type_path) {
+                compiled.components.insert(
+                    type_path.clone(),
+                    CompiledComponent::compile(type_path, registry),
+                );
+            }
+
+            let Some(Some(component)) = compiled.components.get_mut(type_path) else {
+                continue;
+            };
+
+            for track in component_track.values.values() {
+                let binding = track.binding();
+
+                if !component.values.contains_key(&binding.path) {
+                    component.values.insert(
+                        binding.path.clone(),
+                        CompiledValue::compile(binding, component.type_id, registry),
+                    );
+                }
+            }
+        }
+
+        compiled
+    }
+
+    pub fn component(&self, type_path: &ShortTypePath) -> Option<&CompiledComponent> {
+        self.components
+            .get(type_path)
+            .and_then(|component| component.as_ref())
+    }
+}
+
+impl CompiledComponent {
+    fn compile(type_path: &ShortTypePath, registry: &TypeRegistry) -> Option<Self> {
+        let Some(registraion) = registry.get_with_short_type_path(type_path) else {
+            warn!("{:?} not register_type.", type_path);
+            return None;
+        };
+
+        //轨道的类型可以是组件、资源或者目标实体上 Handle 引用的资产
+        if registraion.data::<AnimateComponentFns>().is_none()
+            && registraion.data::<AnimateResourceFns>().is_none()
+            && registraion.data::<AnimateAssetFns>().is_none()
+        {
+            warn!(
+                "{:?} not register_animate_component, register_animate_resource or register_animate_asset.",
+                type_path
+            );
+            return None;
+        }
+
+        Some(CompiledComponent {
+            type_id: registraion.type_id(),
+            values: Default::default(),
+        })
+    }
+}
+
+impl CompiledValue {
+    ///component_type_id 是轨道所在的组件、资源或资产的类型，字段的类型需要和关键帧的类型一致
+    fn compile(
+        binding: &ValueBinding,
+        component_type_id: TypeId,
+        registry: &TypeRegistry,
+    ) -> Option<Self> {
+        let path = match ParsedPath::parse(&binding.path) {
+            Ok(path) => path,
+            Err(e) => {
+                warn!("{} parse error: {}", binding.path, e);
+                return None;
+            }
+        };
+
+        let Some(registraion) = registry.get_with_short_type_path(&binding.value_type) else {
+            warn!("{:?} not register_type.", binding.value_type);
+            return None;
+        };
+
+        if !field_type_matches(component_type_id, &path, registraion.type_id(), registry) {
+            warn!("{} is not a {:?} field.", binding.path, binding.value_type);
+            return None;
+        }
+
+        //没有注册 AnimateValue 的类型只能使用 TrackValue::Reflect 的关键帧
+        let fns = registraion
+            .data::<AnimateValueFns>()
+            .cloned()
+            .unwrap_or_else(AnimateValueFns::reflect_only);
+
+        Some(CompiledValue {
+            path,
+            fns,
+            failed: AtomicBool::new(false),
+        })
+    }
+}
+
+///按类型信息逐级检查路径指向的字段类型是否为 value_type_id
+///
+///经过枚举时取决于运行时的变体，之后的路径不检查
+fn field_type_matches(
+    type_id: TypeId,
+    path: &ParsedPath,
+    value_type_id: TypeId,
+    registry: &TypeRegistry,
+) -> bool {
+    let mut type_id = type_id;
+
+    for access in path.0.iter() {
+        let Some(type_info) = registry.get_type_info(type_id) else {
+            return false;
+        };
+
+        let field_type_id = match (type_info, &access.access) {
+            (TypeInfo::Enum(_), _) => return true,
+            (TypeInfo::Struct(info), Access::Field(name)) => {
+                info.field(name).map(|field| field.type_id())
+            }
+            (TypeInfo::Struct(info), Access::FieldIndex(index)) => {
+                info.field_at(*index).map(|field| field.type_id())
+            }
+            (TypeInfo::TupleStruct(info), Access::TupleIndex(index)) => {
+                info.field_at(*index).map(|field| field.type_id())
+            }
+            (TypeInfo::Tuple(info), Access::TupleIndex(index)) => {
+                info.field_at(*index).map(|field| field.type_id())
+            }
+            (TypeInfo::List(info), Access::ListIndex(_)) => Some(info.item_type_id()),
+            (TypeInfo::Array(info), Access::ListIndex(index)) => {
+                (*index < info.capacity()).then(|| info.item_type_id())
+            }
+            _ => None,
+        };
+
+        let Some(field_type_id) = field_type_id else {
+            return false;
+        };
+
+        type_id = field_type_id;
+    }
+
+    type_id == value_type_id
+}
+
+///所有已加载的 EntityAnimations 编译后的绑定
+#[derive(Default, Resource, Deref, DerefMut)]
+pub struct CompiledAnimations(HashMap<AssetId<EntityAnimations>, CompiledEntityAnimations>);
+
+///资源加载、修改或类型注册变化时重新编译
+pub fn compile_animations(
+    mut events: EventReader<AssetEvent<EntityAnimations>>,
+    animations: Res<Assets<EntityAnimations>>,
+    registry: Res<AppTypeRegistry>,
+    mut compiled: ResMut<CompiledAnimations>,
+) {
+    let type_registry = registry.read();
+
+    if registry.is_changed() {
+        events.clear();
+        compiled.clear();
+
+        for (id, animations) in animations.iter() {
+            compiled.insert(
+                id,
+                CompiledEntityAnimations::compile(animations, &type_registry),
+            );
+        }
+
+        return;
+    }
+
+    for event in events.read() {
+        match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => {
+                if let Some(animations) = animations.get(*id) {
+                    compiled.insert(
+                        *id,
+                        CompiledEntityAnimations::compile(animations, &type_registry),
+                    );
+                }
+            }
+            AssetEvent::Removed { id } => {
+                compiled.remove(id);
+            }
+            _ => {}
+        }
+    }
+}
+
+pub struct EntityAnimationsLoader {
+    registry: TypeRegistryArc,
+}
+
+impl FromWorld for EntityAnimationsLoader {
+    fn from_world(world: &mut World) -> Self {
+        EntityAnimationsLoader {
+            registry: world.resource::<AppTypeRegistry>().0.clone(),
+        }
+    }
+}
+
+impl EntityAnimationsLoader {
+    ///把资源类型的关键帧作为依赖加载，强引用保存在关键帧中，反射类型的关键帧通过类型注册解析
+    fn load_dependencies(&self, animations: &mut EntityAnimations, load_context: &mut LoadContext) {
+        let registry = self.registry.read();
+
+        let tracks = animations
+            .values_mut()
+            .flat_map(|animation| animation.component_tracks_mut())
+            .flat_map(|component_track| component_track.values.values_mut());
+
+        for track in tracks {
+            for key_frame in track.frames.keyframes_mut() {
+                let asset = match &mut key_frame.value {
+                    TrackValue::Asset(asset) => asset,
+                    TrackValue::Reflect(value) => {
+                        if let Err(e) = value.resolve(&registry) {
+                            warn!("{:?} resolve error: {}", value.type_path(), e);
+                        }
+                        continue;
+                    }
+                    _ => continue,
+                };
+
+                let Some(fns) = registry
+                    .get_with_short_type_path(&asset.type_path)
+                    .and_then(|registraion| registraion.data::<AnimateValueFns>())
+                else {
+                    warn!("{:?} not register_animate_value.", asset.type_path);
+                    continue;
+                };
+
+                if let Some(handle) = (fns.load)(asset, load_context) {
+                    asset.set_handle(handle);
+                }
+            }
+        }
+    }
+}
+
+///加载时的设置
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct EntityAnimationsLoaderSettings {
+    //加载时转换为叠加动画的动画和参考姿态的时间(秒)
+    pub additive: HashMap<AnimationName, f32>,
+}
+
+// Possible errors that can be produced by [`CustomAssetLoader`]
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum EntityAnimationsLoaderError {
+    /// An [IO](std::io) Error
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not load asset: {0}")]
+    Serde(#[from] serde_json::Error),
+}
+
+impl AssetLoader for EntityAnimationsLoader {
+    type Asset = EntityAnimations;
+    type Settings = EntityAnimationsLoaderSettings;
+    type Error = EntityAnimationsLoaderError;
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        settings: &'a EntityAnimationsLoaderSettings,
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut custom_asset = serde_json::from_slice::<EntityAnimations>(&bytes)?;
+
+        for (animation_name, reference_time) in settings.additive.iter() {
+            match custom_asset.get_mut(animation_name) {
+                Some(animation) => animation.make_additive(*reference_time),
+                None => warn!("{:?} animation not found.", animation_name),
+            }
+        }
+
+        self.load_dependencies(&mut custom_asset, load_context);
+
+        Ok(custom_asset)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["entity_animations.json"]
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_compile_animations() {
+        use super::{CompiledEntityAnimations, EntityAnimations};
+        use crate::core::{AnimationName, ShortTypePath};
+        use crate::entity::EntityAnimation;
+        use crate::track::{AnimateComponentFns, ComponentTrack, Track};
+        use crate::value::{AnimateValueFns, ValueBinding};
+        use bevy::{prelude::*, reflect::TypeRegistry};
+
+        let track = |path: &str| {
+            Track::new(
+                ValueBinding {
+                    path: path.to_owned(),
+                    value_type: ShortTypePath::from_type_path::<Vec3>(),
+                },
+                1.0,
+            )
+        };
+
+        let mut component_track = ComponentTrack::default();
+        component_track.add_track(track(".translation"));
+        component_track.add_track(track(".translation["));
+        component_track.add_track(track(".scale.w"));
+        component_track.add_track(track(".rotation"));
+
+        let mut animation = EntityAnimation::default();
+        animation.tracks.insert(
+            ShortTypePath::from_type_path::<Transform>(),
+            component_track.clone(),
+        );
+        animation
+            .tracks
+            .insert(ShortTypePath::from_type_path::<Sprite>(), component_track);
+
+        let mut animations = EntityAnimations::default();
+        animations.insert(AnimationName::new("idle"), animation);
+
+        let mut registry = TypeRegistry::default();
+        registry.register::<Transform>();
+        registry.register::<Vec3>();
+        registry.register_type_data::<Transform, AnimateComponentFns>();
+        registry.register_type_data::<Vec3, AnimateValueFns>();
+
+        let compiled = CompiledEntityAnimations::compile(&animations, &registry);
+
+        assert!(compiled
+            .component(&ShortTypePath::from_type_path::<Sprite>())
+            .is_none());
+
+        let transform = compiled
+            .component(&ShortTypePath::from_type_path::<Transform>())
+            .unwrap();
+        assert!(transform.value(".translation").is_some());
+        assert!(transform.value(".translation[").is_none());
+        //字段不存在或者类型和关键帧不一致
+        assert!(transform.value(".scale.w").is_none());
+        assert!(transform.value(".rotation").is_none());
+
+        //写入失败后跳过
+        transform.value(".translation").unwrap().set_failed();
+        assert!(transform.value(".translation").is_none());
+    }
+}
@@ -0,0 +1,489 @@
+use std::sync::RwLock;
+
+use bevy::math::{DVec2, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    assets::{CompiledEntityAnimations, EntityAnimations},
+    core::{AnimationName, EntityPath},
+    entity::BoundAnimationPose,
+    state_machine::AnimationParameters,
+};
+
+///混合空间中的一个动画
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlendClip {
+    pub animation: AnimationName,
+    pub weight: f32,
+    //动画时长，由 advance_animations 解析
+    pub(crate) duration: f32,
+}
+
+impl BlendClip {
+    pub fn new(animation: AnimationName, weight: f32) -> Self {
+        Self {
+            animation,
+            weight,
+            duration: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BlendSample1D {
+    pub animation: AnimationName,
+    pub position: f32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BlendSample2D {
+    pub animation: AnimationName,
+    pub position: Vec2,
+}
+
+///根据参数在多个动画之间混合，1D 按线段插值，2D 按 Delaunay 三角剖分中三角形的重心坐标插值
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum BlendSpace {
+    OneD {
+        parameter: String,
+        samples: Vec<BlendSample1D>,
+    },
+    TwoD {
+        parameter_x: String,
+        parameter_y: String,
+        samples: Vec<BlendSample2D>,
+        //样本位置的三角剖分，第一次计算权重时生成，样本修改后重新生成
+        #[serde(skip)]
+        triangles: Triangulation,
+    },
+}
+
+///缓存的样本位置和对应的三角剖分
+#[derive(Debug, Default)]
+pub struct Triangulation(RwLock<(Vec<Vec2>, Vec<[usize; 3]>)>);
+
+impl Clone for Triangulation {
+    fn clone(&self) -> Self {
+        Self(RwLock::new(self.0.read().unwrap().clone()))
+    }
+}
+
+impl Triangulation {
+    ///按 positions 的三角剖分计算权重，和缓存的样本位置不一致时重新三角剖分
+    pub fn weights(&self, positions: &[Vec2], point: Vec2) -> Vec<(usize, f32)> {
+        {
+            let cache = self.0.read().unwrap();
+
+            if cache.0 == positions {
+                return weights_2d(positions, &cache.1, point);
+            }
+        }
+
+        let mut cache = self.0.write().unwrap();
+
+        if cache.0 != positions {
+            *cache = (positions.to_vec(), triangulate(positions));
+        }
+
+        weights_2d(positions, &cache.1, point)
+    }
+}
+
+impl BlendSpace {
+    ///根据参数计算各个动画的权重，参数不存在时为 0
+    pub fn clips(&self, parameters: &AnimationParameters) -> Vec<BlendClip> {
+        let parameter = |name: &str| parameters.get_float(name).unwrap_or(0.0);
+
+        match self {
+            BlendSpace::OneD {
+                parameter: x,
+                samples,
+            } => {
+                let positions = samples
+                    .iter()
+                    .map(|sample| sample.position)
+                    .collect::<Vec<_>>();
+
+                weights_1d(&positions, parameter(x))
+                    .into_iter()
+                    .map(|(index, weight)| BlendClip::new(samples[index].animation.clone(), weight))
+                    .collect()
+            }
+            BlendSpace::TwoD {
+                parameter_x,
+                parameter_y,
+                samples,
+                triangles,
+            } => {
+                let positions = samples
+                    .iter()
+                    .map(|sample| sample.position)
+                    .collect::<Vec<_>>();
+                let point = Vec2::new(parameter(parameter_x), parameter(parameter_y));
+
+                triangles
+                    .weights(&positions, point)
+                    .into_iter()
+                    .map(|(index, weight)| BlendClip::new(samples[index].animation.clone(), weight))
+                    .collect()
+            }
+        }
+    }
+}
+
+///1D 混合的权重，超出范围时使用最近的端点
+pub fn weights_1d(positions: &[f32], x: f32) -> Vec<(usize, f32)> {
+    let mut indexs = (0..positions.len()).collect::<Vec<_>>();
+    indexs.sort_by(|a, b| positions[*a].total_cmp(&positions[*b]));
+
+    let (Some(first), Some(last)) = (indexs.first(), indexs.last()) else {
+        return vec![];
+    };
+
+    if x <= positions[*first] {
+        return vec![(*first, 1.0)];
+    }
+
+    if x >= positions[*last] {
+        return vec![(*last, 1.0)];
+    }
+
+    for segment in indexs.windows(2) {
+        let (a, b) = (positions[segment[0]], positions[segment[1]]);
+
+        if x >= a && x <= b {
+            let t = if b > a { (x - a) / (b - a) } else { 0.0 };
+            return non_zero(vec![(segment[0], 1.0 - t), (segment[1], t)]);
+        }
+    }
+
+    vec![(*last, 1.0)]
+}
+
+///2D 混合的权重，使用包含该点的三角形，不在任何三角形内时投影到最近的边上
+///
+///triangles 是 positions 的三角剖分，三角形之间不重叠，参数连续变化时权重也连续变化
+pub fn weights_2d(positions: &[Vec2], triangles: &[[usize; 3]], point: Vec2) -> Vec<(usize, f32)> {
+    match positions.len() {
+        0 => return vec![],
+        1 => return vec![(0, 1.0)],
+        _ => {}
+    }
+
+    for [i, j, k] in triangles.iter().copied() {
+        let Some(weights) = barycentric(positions[i], positions[j], positions[k], point) else {
+            continue;
+        };
+
+        if weights.iter().any(|weight| *weight < -1e-5) {
+            continue;
+        }
+
+        let weights = weights.map(|weight| weight.max(0.0));
+        let total = weights.iter().sum::<f32>();
+        return non_zero(vec![
+            (i, weights[0] / total),
+            (j, weights[1] / total),
+            (k, weights[2] / total),
+        ]);
+    }
+
+    //三角剖分的边界就是所有三角形的边，所有点共线时没有三角形，使用任意两点之间的线段
+    let edges = if triangles.is_empty() {
+        (0..positions.len())
+            .flat_map(|i| ((i + 1)..positions.len()).map(move |j| (i, j)))
+            .collect::<Vec<_>>()
+    } else {
+        triangles
+            .iter()
+            .flat_map(|[i, j, k]| [(*i, *j), (*j, *k), (*k, *i)])
+            .collect()
+    };
+
+    let mut nearest = (f32::MAX, 0, 0, 0.0);
+
+    for (i, j) in edges {
+        let (a, b) = (positions[i], positions[j]);
+        let length = (b - a).length_squared();
+        let t = if length > 0.0 {
+            ((point - a).dot(b - a) / length).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let distance = a.lerp(b, t).distance(point);
+
+        if distance < nearest.0 {
+            nearest = (distance, i, j, t);
+        }
+    }
+
+    let (_, i, j, t) = nearest;
+    non_zero(vec![(i, 1.0 - t), (j, t)])
+}
+
+///Bowyer-Watson 算法生成 Delaunay 三角剖分，重复的点只使用第一个，共线的点不生成三角形
+pub fn triangulate(positions: &[Vec2]) -> Vec<[usize; 3]> {
+    if positions.len() < 3 {
+        return vec![];
+    }
+
+    let mut points = positions
+        .iter()
+        .map(|position| position.as_dvec2())
+        .collect::<Vec<_>>();
+
+    //包含所有点的超级三角形，顶点在 points 的末尾
+    let min = points.iter().copied().reduce(DVec2::min).unwrap();
+    let max = points.iter().copied().reduce(DVec2::max).unwrap();
+    let center = (min + max) * 0.5;
+    let size = (max - min).max_element().max(1.0) * 100.0;
+    let n = points.len();
+    points.push(center + DVec2::new(-size, -size));
+    points.push(center + DVec2::new(size, -size));
+    points.push(center + DVec2::new(0.0, size));
+
+    let mut triangles: Vec<[usize; 3]> = vec![[n, n + 1, n + 2]];
+
+    for index in 0..n {
+        let point = points[index];
+
+        if points[..index].contains(&point) {
+            continue;
+        }
+
+        let (bad, good): (Vec<_>, Vec<_>) = triangles
+            .into_iter()
+            .partition(|triangle| in_circumcircle(&points, *triangle, point));
+
+        //只属于一个待删除三角形的边组成了需要重新连接的空腔边界
+        let edges = bad
+            .iter()
+            .flat_map(|[a, b, c]| [(*a, *b), (*b, *c), (*c, *a)])
+            .collect::<Vec<_>>();
+
+        triangles = good;
+
+        for (a, b) in edges.iter().copied() {
+            let shared = edges
+                .iter()
+                .filter(|(c, d)| (*c == a && *d == b) || (*c == b && *d == a))
+                .count();
+
+            if shared == 1 {
+                triangles.push([a, b, index]);
+            }
+        }
+    }
+
+    triangles
+        .into_iter()
+        .filter(|triangle| triangle.iter().all(|index| *index < n))
+        .filter(|[a, b, c]| {
+            (points[*b] - points[*a])
+                .perp_dot(points[*c] - points[*a])
+                .abs()
+                > 1e-9
+        })
+        .collect()
+}
+
+///point 是否在三角形的外接圆内，退化的三角形没有外接圆，总是需要删除
+fn in_circumcircle(points: &[DVec2], [a, b, c]: [usize; 3], point: DVec2) -> bool {
+    let (a, b, c) = (points[a], points[b], points[c]);
+    let d = 2.0 * (b - a).perp_dot(c - a);
+
+    if d.abs() < 1e-12 {
+        return true;
+    }
+
+    let (ab, ac) = (b - a, c - a);
+    let center = a + DVec2::new(
+        ac.y * ab.length_squared() - ab.y * ac.length_squared(),
+        ab.x * ac.length_squared() - ac.x * ab.length_squared(),
+    ) / d;
+
+    point.distance_squared(center) < a.distance_squared(center)
+}
+
+fn barycentric(a: Vec2, b: Vec2, c: Vec2, point: Vec2) -> Option<[f32; 3]> {
+    let det = (b.y - c.y) * (a.x - c.x) + (c.x - b.x) * (a.y - c.y);
+
+    if det.abs() < 1e-6 {
+        return None;
+    }
+
+    let u = ((b.y - c.y) * (point.x - c.x) + (c.x - b.x) * (point.y - c.y)) / det;
+    let v = ((c.y - a.y) * (point.x - c.x) + (a.x - c.x) * (point.y - c.y)) / det;
+
+    Some([u, v, 1.0 - u - v])
+}
+
+fn non_zero(weights: Vec<(usize, f32)>) -> Vec<(usize, f32)> {
+    weights
+        .into_iter()
+        .filter(|(_, weight)| *weight > 0.0)
+        .collect()
+}
+
+///按归一化进度同步采样所有动画并按权重混合写入 pose，没有可以采样的动画时返回 false
+///
+///clips 按权重从大到小排列，无法插值的值(例如资源和 usize)使用权重最大的动画，
+///除第一个动画外其他动画采样到 clip_poses 中，缓冲区在帧之间复用
+pub(crate) fn sample_blend_into(
+    clips: &[BlendClip],
+    phase: f32,
+    animations: &EntityAnimations,
+    compiled: Option<&CompiledEntityAnimations>,
+    path: Option<&EntityPath>,
+    pose: &mut BoundAnimationPose,
+    clip_poses: &mut Vec<BoundAnimationPose>,
+) -> bool {
+    let mut sampled = false;
+    let mut total = 0.0;
+
+    clip_poses.resize_with(clips.len().saturating_sub(1), Default::default);
+
+    for (index, clip) in clips.iter().enumerate() {
+        let Some(animation) = animations.get(&clip.animation) else {
+            continue;
+        };
+
+        let time = phase * animation.duration();
+
+        total += clip.weight;
+
+        if !sampled {
+            animation.sample_path_into(path, time, compiled, pose);
+            sampled = true;
+            continue;
+        }
+
+        let clip_pose = &mut clip_poses[index - 1];
+        animation.sample_path_into(path, time, compiled, clip_pose);
+
+        if total > 0.0 {
+            pose.blend_with(clip_pose, clip.weight / total, f32::INFINITY);
+        }
+    }
+
+    sampled
+}
+
+mod test {
+
+    #[test]
+    fn test_weights_1d() {
+        use super::weights_1d;
+
+        let positions = [0.0, 2.0, 1.0];
+
+        assert_eq!(weights_1d(&positions, -1.0), vec![(0, 1.0)]);
+        assert_eq!(weights_1d(&positions, 3.0), vec![(1, 1.0)]);
+        assert_eq!(weights_1d(&positions, 1.0), vec![(2, 1.0)]);
+        assert_eq!(weights_1d(&positions, 1.5), vec![(2, 0.5), (1, 0.5)]);
+    }
+
+    #[test]
+    fn test_weights_2d() {
+        use super::{triangulate, weights_2d};
+        use bevy::math::Vec2;
+
+        let positions = [Vec2::ZERO, Vec2::X, Vec2::Y, Vec2::NEG_X, Vec2::NEG_Y];
+        let triangles = triangulate(&positions);
+        assert_eq!(triangles.len(), 4);
+
+        let weights = weights_2d(&positions, &triangles, Vec2::new(0.25, 0.25));
+        assert_eq!(weights.len(), 3);
+        for (index, weight) in weights {
+            let expected = if index == 0 { 0.5 } else { 0.25 };
+            assert!((weight - expected).abs() < 1e-5);
+        }
+
+        let weights = weights_2d(&positions, &triangles, Vec2::new(2.0, 0.0));
+        assert_eq!(weights, vec![(1, 1.0)]);
+    }
+
+    #[test]
+    fn test_weights_2d_continuous() {
+        use super::{triangulate, weights_2d};
+        use bevy::math::Vec2;
+
+        //所有三角形互相重叠的样本，参数连续移动时权重不应该跳变
+        let positions = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(4.0, 0.0),
+            Vec2::new(0.0, 4.0),
+            Vec2::new(4.0, 4.0),
+            Vec2::new(2.0, 1.0),
+        ];
+        let triangles = triangulate(&positions);
+        assert_eq!(triangles.len(), 4);
+
+        let weights_at = |point: Vec2| {
+            let mut weights = [0.0; 5];
+            for (index, weight) in weights_2d(&positions, &triangles, point) {
+                weights[index] = weight;
+            }
+            weights
+        };
+
+        let mut last = weights_at(Vec2::new(-1.0, 0.5));
+        for step in 1..=600 {
+            let point = Vec2::new(-1.0, 0.5).lerp(Vec2::new(5.0, 3.5), step as f32 / 600.0);
+            let weights = weights_at(point);
+            for (a, b) in weights.iter().zip(last.iter()) {
+                assert!((a - b).abs() < 0.05, "{:?} {:?} {:?}", point, weights, last);
+            }
+            last = weights;
+        }
+
+        //共线的样本没有三角形，投影到线段上
+        let positions = [Vec2::ZERO, Vec2::X, Vec2::X * 2.0];
+        assert!(triangulate(&positions).is_empty());
+        assert_eq!(
+            weights_2d(&positions, &[], Vec2::new(0.5, 1.0)),
+            vec![(0, 0.5), (1, 0.5)]
+        );
+    }
+
+    #[test]
+    fn test_modify_samples() {
+        use super::{BlendSample2D, BlendSpace};
+        use crate::core::AnimationName;
+        use crate::state_machine::AnimationParameters;
+        use bevy::math::Vec2;
+
+        let sample = |animation: &str, position: Vec2| BlendSample2D {
+            animation: AnimationName::new(animation),
+            position,
+        };
+
+        let mut blend_space = BlendSpace::TwoD {
+            parameter_x: "x".to_string(),
+            parameter_y: "y".to_string(),
+            samples: vec![
+                sample("idle", Vec2::ZERO),
+                sample("right", Vec2::X),
+                sample("up", Vec2::Y),
+                sample("left", Vec2::NEG_X),
+            ],
+            triangles: Default::default(),
+        };
+
+        let mut parameters = AnimationParameters::default();
+        parameters.set_float("x", -0.5);
+        assert_eq!(blend_space.clips(&parameters).len(), 2);
+
+        //修改样本后按新的样本重新三角剖分
+        let BlendSpace::TwoD { samples, .. } = &mut blend_space else {
+            unreachable!();
+        };
+        samples.truncate(2);
+        samples[1].position = Vec2::NEG_X;
+
+        let clips = blend_space.clips(&parameters);
+        assert_eq!(clips.len(), 2);
+        assert!(clips.iter().all(|clip| (clip.weight - 0.5).abs() < 1e-5));
+        assert_eq!(clips[1].animation, AnimationName::new("right"));
+    }
+}
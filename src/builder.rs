@@ -1,37 +1,284 @@
-use crate::{assets::EntityAnimations, plugin::NextAnimationTarget};
-use bevy::{prelude::*, utils::HashMap};
-
-#[derive(Bundle)]
-pub struct AnimationBundle {
-    target: NextAnimationTarget,
-    handle: Handle<EntityAnimations>,
-}
-
-pub struct AnimationsBuilder {
-    target: Entity,
-    data: HashMap<String, Handle<EntityAnimations>>,
-}
-
-impl AnimationsBuilder {
-    pub fn entity(entity: Entity) -> Self {
-        Self {
-            target: entity,
-            data: Default::default(),
-        }
-    }
-
-    pub fn add_handle(&mut self, entity_class: &str, handle: Handle<EntityAnimations>) {
-        self.data.insert(entity_class.to_string(), handle);
-    }
-
-    pub fn get_animation_bundle(&self, entity_class: &str) -> Option<AnimationBundle> {
-        self.data.get(entity_class).and_then(|handle| {
-            Some(AnimationBundle {
-                target: NextAnimationTarget {
-                    player: self.target,
-                },
-                handle: handle.clone(),
-            })
-        })
-    }
-}
+use crate::{
+    assets::EntityAnimations,
+    core::AnimationName,
+    plugin::{NextAnimationPlayer, NextAnimationTarget},
+};
+use bevy::{prelude::*, utils::HashMap};
+
+#[derive(Bundle)]
+pub struct AnimationBundle {
+    target: NextAnimationTarget,
+    handle: Handle<EntityAnimations>,
+}
+
+pub struct AnimationsBuilder {
+    target: Entity,
+    data: HashMap<String, Handle<EntityAnimations>>,
+}
+
+impl AnimationsBuilder {
+    pub fn entity(entity: Entity) -> Self {
+        Self {
+            target: entity,
+            data: Default::default(),
+        }
+    }
+
+    pub fn add_handle(&mut self, entity_class: &str, handle: Handle<EntityAnimations>) {
+        self.data.insert(entity_class.to_string(), handle);
+    }
+
+    pub fn get_animation_bundle(&self, entity_class: &str) -> Option<AnimationBundle> {
+        self.data.get(entity_class).map(|handle| AnimationBundle {
+            target: NextAnimationTarget::new(self.target, entity_class),
+            handle: handle.clone(),
+        })
+    }
+
+    ///所有实体类别的组件，插入到对应类别的实体上，由同一个播放器驱动
+    pub fn animation_bundles(&self) -> impl Iterator<Item = (&str, AnimationBundle)> + '_ {
+        self.data.iter().map(|(entity_class, handle)| {
+            (
+                entity_class.as_str(),
+                AnimationBundle {
+                    target: NextAnimationTarget::new(self.target, entity_class),
+                    handle: handle.clone(),
+                },
+            )
+        })
+    }
+}
+
+///播放器驱动的所有目标，按实体类别分组
+#[derive(Debug, Default, Component)]
+pub struct NextAnimationTargets {
+    classes: HashMap<String, Vec<Entity>>,
+}
+
+impl NextAnimationTargets {
+    pub fn classes(&self) -> impl Iterator<Item = &str> {
+        self.classes.keys().map(|class| class.as_str())
+    }
+
+    ///类别下的所有目标
+    pub fn targets(&self, class: &str) -> &[Entity] {
+        self.classes
+            .get(class)
+            .map(|targets| targets.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.classes.values().flatten().copied()
+    }
+
+    ///包含该动画的目标中最长的时长，和结束判断一致，动画还没有加载时为 None
+    pub fn duration(
+        &self,
+        name: &AnimationName,
+        handle_q: &Query<&Handle<EntityAnimations>>,
+        animations: &Assets<EntityAnimations>,
+    ) -> Option<f32> {
+        self.iter()
+            .filter_map(|entity| handle_q.get(entity).ok())
+            .filter_map(|handle| animations.get(handle)?.get(name))
+            .map(|animation| animation.duration())
+            .reduce(f32::max)
+    }
+}
+
+///目标变化时重新收集每个播放器的目标
+pub fn collect_animation_targets(
+    mut commands: Commands,
+    target_q: Query<(Entity, &NextAnimationTarget)>,
+    changed_q: Query<(), Changed<NextAnimationTarget>>,
+    added_q: Query<(), Added<NextAnimationPlayer>>,
+    mut removed: RemovedComponents<NextAnimationTarget>,
+    mut player_q: Query<(Entity, Option<&mut NextAnimationTargets>), With<NextAnimationPlayer>>,
+) {
+    //播放器晚于目标添加时也需要收集
+    if changed_q.is_empty() && added_q.is_empty() && removed.read().count() == 0 {
+        return;
+    }
+
+    let mut players: HashMap<Entity, NextAnimationTargets> = HashMap::default();
+
+    for (entity, target) in target_q.iter() {
+        players
+            .entry(target.player)
+            .or_default()
+            .classes
+            .entry(target.class.clone())
+            .or_default()
+            .push(entity);
+    }
+
+    for (player, targets) in player_q.iter_mut() {
+        let mut collected = players.remove(&player).unwrap_or_default();
+
+        for entities in collected.classes.values_mut() {
+            entities.sort();
+        }
+
+        match targets {
+            Some(mut targets) => *targets = collected,
+            None => {
+                commands.entity(player).insert(collected);
+            }
+        }
+    }
+
+    //播放器组件可能稍后添加，只报告不存在的实体
+    for player in players.keys() {
+        if commands.get_entity(*player).is_none() {
+            warn!("{} player entity not found.", player);
+        }
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_collect_targets() {
+        use super::{collect_animation_targets, AnimationsBuilder, NextAnimationTargets};
+        use crate::{assets::EntityAnimations, plugin::NextAnimationPlayer};
+        use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+        let mut world = World::new();
+
+        let player = world.spawn(NextAnimationPlayer::default()).id();
+        let body = world.spawn_empty().id();
+        let weapon = world.spawn_empty().id();
+
+        let mut builder = AnimationsBuilder::entity(player);
+        builder.add_handle("body", Handle::<EntityAnimations>::default());
+        builder.add_handle("weapon", Handle::<EntityAnimations>::default());
+
+        for (class, bundle) in builder.animation_bundles() {
+            let entity = if class == "body" { body } else { weapon };
+            world.entity_mut(entity).insert(bundle);
+        }
+
+        world.run_system_once(collect_animation_targets);
+
+        let targets = world.get::<NextAnimationTargets>(player).unwrap();
+        assert_eq!(targets.targets("body"), &[body]);
+        assert_eq!(targets.targets("weapon"), &[weapon]);
+        assert_eq!(targets.iter().count(), 2);
+
+        world.despawn(weapon);
+        world.run_system_once(collect_animation_targets);
+
+        let targets = world.get::<NextAnimationTargets>(player).unwrap();
+        assert!(targets.targets("weapon").is_empty());
+    }
+
+    #[test]
+    fn test_play_classes() {
+        use super::AnimationsBuilder;
+        use crate::assets::EntityAnimations;
+        use crate::core::{AnimationName, ShortTypePath};
+        use crate::entity::EntityAnimation;
+        use crate::plugin::{AnimationExt, BevyNextAnimationPlugin, NextAnimationPlayer};
+        use crate::track::{ComponentTrack, Keyframe, Track};
+        use crate::value::{TrackValue, ValueBinding};
+        use bevy::ecs::schedule::ExecutorKind;
+        use bevy::log::tracing_subscriber::{layer::Context, prelude::*, registry, Layer};
+        use bevy::prelude::*;
+        use bevy::utils::tracing::{subscriber, Event, Level, Subscriber};
+        use bevy::utils::HashMap;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        //统计这个线程上输出的警告
+        struct WarnCounter(Arc<AtomicUsize>);
+
+        impl<S: Subscriber> Layer<S> for WarnCounter {
+            fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+                if *event.metadata().level() == Level::WARN {
+                    self.0.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+
+        #[derive(Component, Reflect, Default)]
+        struct TestValue {
+            value: f32,
+        }
+
+        let animations = |name: &str| {
+            let mut track = Track::new(
+                ValueBinding {
+                    path: ".value".to_owned(),
+                    value_type: ShortTypePath::from_type_path::<f32>(),
+                },
+                1.0,
+            );
+            track.add_keyframe(Keyframe::new(0.0, TrackValue::Number(1.0)));
+
+            let mut component_track = ComponentTrack::default();
+            component_track.add_track(track);
+
+            let mut animation = EntityAnimation::default();
+            animation.tracks.insert(
+                ShortTypePath::from_type_path::<TestValue>(),
+                component_track,
+            );
+
+            let mut animations = EntityAnimations::default();
+            animations.insert(AnimationName::new(name), animation);
+            animations
+        };
+
+        let warnings = Arc::new(AtomicUsize::new(0));
+        let _guard = subscriber::set_default(registry().with(WarnCounter(warnings.clone())));
+
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            BevyNextAnimationPlugin,
+        ))
+        .register_type::<TestValue>()
+        .register_animate_component::<TestValue>();
+        app.edit_schedule(PostUpdate, |schedule| {
+            schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        });
+
+        let player = app.world_mut().spawn_empty().id();
+        let mut builder = AnimationsBuilder::entity(player);
+
+        for (class, name) in [("body", "attack"), ("weapon", "attack"), ("shield", "idle")] {
+            let handle = app
+                .world_mut()
+                .resource_mut::<Assets<EntityAnimations>>()
+                .add(animations(name));
+            builder.add_handle(class, handle);
+        }
+
+        let mut targets = HashMap::new();
+        for (class, bundle) in builder.animation_bundles() {
+            let entity = app.world_mut().spawn((TestValue::default(), bundle)).id();
+            targets.insert(class.to_string(), entity);
+        }
+
+        app.update();
+
+        //目标添加之后才添加播放器
+        let mut next_player = NextAnimationPlayer::default();
+        next_player.play("attack");
+        app.world_mut().entity_mut(player).insert(next_player);
+
+        for _ in 0..5 {
+            app.update();
+        }
+
+        let value = |class: &str| app.world().get::<TestValue>(targets[class]).unwrap().value;
+        assert_eq!(value("body"), 1.0);
+        assert_eq!(value("weapon"), 1.0);
+        assert_eq!(value("shield"), 0.0);
+
+        //shield 类别没有 attack 动画，只警告一次
+        assert_eq!(warnings.load(Ordering::Relaxed), 1);
+    }
+}
@@ -31,3 +31,18 @@ impl ShortTypePath {
         Self(T::short_type_path().to_string())
     }
 }
+
+///由子实体的 Name 组成的路径，例如 body/arm_r
+#[derive(Debug, Default, Hash, PartialEq, Eq, Clone, Deref, DerefMut, Deserialize, Serialize)]
+pub struct EntityPath(String);
+
+impl EntityPath {
+    pub fn new(path: &str) -> Self {
+        EntityPath(path.to_string())
+    }
+
+    ///路径上每一级子实体的 Name
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.split('/').filter(|name| !name.is_empty())
+    }
+}
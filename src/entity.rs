@@ -1,95 +1,572 @@
-use crate::{
-    assets::EntityAnimations,
-    core::{AnimationName, ShortTypePath},
-    prelude::{AnimateComponentFns, ComponentPose},
-    track::ComponentTrack,
-};
-use bevy::{prelude::*, reflect::TypeRegistry, utils::HashMap};
-use serde::{Deserialize, Serialize};
-
-#[derive(Clone)]
-pub struct ReflectComponent {
-    pub pose: ComponentPose,
-    pub apply: AnimateComponentFns,
-}
-
-#[derive(Default, Clone, Deref, Deserialize, Serialize)]
-pub struct EntityAnimation {
-    pub tracks: HashMap<ShortTypePath, ComponentTrack>,
-}
-
-impl EntityAnimation {
-    pub fn get_animation_pose(
-        &self,
-        dt: f32,
-        registry: &TypeRegistry,
-        asset_server: &AssetServer,
-    ) -> AnimationPose {
-        let mut pose = AnimationPose::default();
-
-        for (type_path, track) in self.tracks.iter() {
-            if let Some(registraion) = registry.get_with_short_type_path(&type_path) {
-                if let Some(apply) = registraion.data::<AnimateComponentFns>() {
-                    let collection = track.fetch(dt);
-                    if let Some(component_pose) =
-                        collection.get_component_pose(registry, asset_server)
-                    {
-                        pose.insert(
-                            type_path.clone(),
-                            ReflectComponent {
-                                pose: component_pose,
-                                apply: apply.clone(),
-                            },
-                        );
-                    }
-                } else {
-                    warn!("{:?} not register_animate_component.", type_path);
-                }
-            } else {
-                warn!("{:?} not register_type.", type_path);
-            }
-        }
-
-        pose
-    }
-}
-
-#[derive(Deref, DerefMut, Default, Clone)]
-pub struct AnimationPose(pub HashMap<ShortTypePath, ReflectComponent>);
-
-#[derive(Component, Clone)]
-pub struct NextAnimation {
-    pose: AnimationPose,
-}
-
-impl NextAnimation {
-    pub fn new(
-        registry: &TypeRegistry,
-        asset_server: &AssetServer,
-        animations: &Assets<EntityAnimations>,
-        handle: &Handle<EntityAnimations>,
-        active_name: &AnimationName,
-        dt: f32,
-    ) -> Option<Self> {
-        animations.get(handle).and_then(|animations| {
-            animations.get(active_name).and_then(|animation| {
-                let pose = animation.get_animation_pose(dt, registry, asset_server);
-
-                Some(NextAnimation { pose })
-            })
-        })
-    }
-}
-
-pub struct EntityAnimationContext<'a> {
-    pub entity_world: EntityWorldMut<'a>,
-    pub animation: NextAnimation,
-}
-
-impl<'a> EntityAnimationContext<'a> {
-    pub fn apply(mut self) {
-        for (_, component) in self.animation.pose.0.into_iter() {
-            (component.apply.apply)(&mut self.entity_world, component.pose);
-        }
-    }
-}
+use crate::{
+    assets::{CompiledEntityAnimations, EntityAnimations},
+    core::{EntityPath, ShortTypePath},
+    layer::{AnimationLayer, LayerBlendMode},
+    track::{BoundComponentValue, ComponentTrack, LoopMode, Marker, MarkerTrack},
+    value::BoundValue,
+};
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+use std::{marker::PhantomData, ops::DerefMut};
+
+#[derive(Default, Clone, Deref, Deserialize, Serialize)]
+pub struct EntityAnimation {
+    #[deref]
+    pub tracks: HashMap<ShortTypePath, ComponentTrack>,
+    //动画的循环模式，轨道可以单独覆盖
+    #[serde(default)]
+    pub loop_mode: LoopMode,
+    //标记轨道，播放经过标记时触发事件
+    #[serde(default, skip_serializing_if = "MarkerTrack::is_empty")]
+    pub markers: MarkerTrack,
+    //叠加动画，关键帧为相对参考姿态的差值，作为动画层时叠加在下层之上
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub additive: bool,
+    //子实体的轨道，按路径在播放器实体下查找
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub children: HashMap<EntityPath, HashMap<ShortTypePath, ComponentTrack>>,
+}
+
+impl EntityAnimation {
+    ///动画时长，为所有轨道中最长的时长
+    pub fn duration(&self) -> f32 {
+        self.component_tracks()
+            .flat_map(|(_, track)| track.values.values())
+            .map(|track| track.frames.duration())
+            .fold(0.0, f32::max)
+    }
+
+    ///转换为相对 reference_time 时刻姿态的叠加动画，已经是叠加动画时不做修改
+    pub fn make_additive(&mut self, reference_time: f32) {
+        if self.additive {
+            return;
+        }
+
+        let loop_mode = self.loop_mode;
+
+        for track in self.component_tracks_mut() {
+            track.make_additive(reference_time, loop_mode);
+        }
+
+        self.additive = true;
+    }
+
+    ///目标实体和所有子实体的组件轨道
+    pub fn component_tracks(&self) -> impl Iterator<Item = (&ShortTypePath, &ComponentTrack)> {
+        self.tracks
+            .iter()
+            .chain(self.children.values().flat_map(|tracks| tracks.iter()))
+    }
+
+    pub fn component_tracks_mut(&mut self) -> impl Iterator<Item = &mut ComponentTrack> {
+        self.tracks.values_mut().chain(
+            self.children
+                .values_mut()
+                .flat_map(|tracks| tracks.values_mut()),
+        )
+    }
+
+    ///path 为 None 时为目标实体的轨道，否则为对应子实体的轨道
+    pub fn tracks_at(
+        &self,
+        path: Option<&EntityPath>,
+    ) -> Option<&HashMap<ShortTypePath, ComponentTrack>> {
+        match path {
+            None => Some(&self.tracks),
+            Some(path) => self.children.get(path),
+        }
+    }
+
+    ///按动画的循环模式判断是否播放结束
+    pub fn is_finished(&self, time: f32) -> bool {
+        self.loop_mode.is_finished(time, self.duration())
+    }
+
+    ///播放时间从 from 变化到 to 时经过的标记，反向播放时按反向经过的顺序返回
+    pub fn crossed_markers(&self, from: f32, to: f32) -> Vec<&Marker> {
+        if from <= to {
+            self.markers
+                .crossed(from, to, self.loop_mode, self.duration())
+        } else {
+            let mut markers = self
+                .markers
+                .crossed(to, from, self.loop_mode, self.duration());
+            markers.reverse();
+            markers
+        }
+    }
+
+    ///按动画的循环模式计算已完成的循环次数
+    pub fn loop_count(&self, time: f32) -> u32 {
+        self.loop_mode.loop_count(time, self.duration())
+    }
+
+    ///采样 time 时刻所有轨道的关键帧数据
+    pub fn sample_pose(&self, time: f32) -> BoundAnimationPose {
+        let mut pose = BoundAnimationPose::default();
+        self.sample_pose_into(time, &mut pose);
+        pose
+    }
+
+    ///采样 time 时刻所有轨道的关键帧数据写入 pose，复用 pose 中已有的缓冲区
+    pub fn sample_pose_into(&self, time: f32, pose: &mut BoundAnimationPose) {
+        self.sample_path_into(None, time, None, pose);
+    }
+
+    ///采样 time 时刻 path 对应实体的轨道写入 pose，没有该实体的轨道时 pose 为空
+    ///
+    ///compiled 为 None 时所有字段都按可以插值处理
+    pub fn sample_path_into(
+        &self,
+        path: Option<&EntityPath>,
+        time: f32,
+        compiled: Option<&CompiledEntityAnimations>,
+        pose: &mut BoundAnimationPose,
+    ) {
+        let Some(tracks) = self.tracks_at(path) else {
+            pose.clear();
+            return;
+        };
+
+        pose.retain(|type_path, _| tracks.contains_key(type_path));
+
+        for (type_path, track) in tracks.iter() {
+            let compiled = compiled.and_then(|compiled| compiled.component(type_path));
+
+            match pose.get_mut(type_path) {
+                Some(component) => track.fetch_into(time, self.loop_mode, compiled, component),
+                None => {
+                    pose.insert(
+                        type_path.clone(),
+                        track.fetch(time, self.loop_mode, compiled),
+                    );
+                }
+            }
+        }
+    }
+}
+
+///尚未转换为反射数据的动画状态，可以在不同动画之间混合
+#[derive(Deref, DerefMut, Default, Clone)]
+pub struct BoundAnimationPose(pub HashMap<ShortTypePath, BoundComponentValue>);
+
+impl BoundAnimationPose {
+    ///按组件和字段路径与 other 混合，weight 为 1 时等于 other
+    pub fn blend_with(&mut self, other: &Self, weight: f32, threshold: f32) {
+        for (type_path, other_component) in other.iter() {
+            if let Some(component) = self.get_mut(type_path) {
+                component.blend_with(other_component, weight, threshold);
+            } else {
+                self.insert(type_path.clone(), other_component.clone());
+            }
+        }
+    }
+
+    ///按 weight 叠加 other，self 中没有的组件无法叠加会被忽略
+    pub fn add_with(&mut self, other: &Self, weight: f32, threshold: f32) {
+        for (type_path, other_component) in other.iter() {
+            if let Some(component) = self.get_mut(type_path) {
+                component.add_with(other_component, weight, threshold);
+            }
+        }
+    }
+
+    ///结果与 from.blend_with(self) 相同，但写入 self，用于复用 self 的缓冲区
+    pub fn blend_from(&mut self, from: &Self, weight: f32, threshold: f32) {
+        for (type_path, from_component) in from.iter() {
+            if let Some(component) = self.get_mut(type_path) {
+                component.blend_from(from_component, weight, threshold);
+            } else {
+                self.insert(type_path.clone(), from_component.clone());
+            }
+        }
+    }
+
+    ///按层的权重、混合方式和遮罩把动画层合并到当前的状态，叠加动画总是叠加
+    pub fn apply_layer(
+        &mut self,
+        layer: &AnimationLayer,
+        additive: bool,
+        layer_pose: &Self,
+        threshold: f32,
+    ) {
+        for (type_path, layer_component) in layer_pose.iter() {
+            let filter = |value: &BoundValue| layer.mask.allows(type_path, &value.binding.path);
+
+            if additive || layer.blend_mode == LayerBlendMode::Additive {
+                if let Some(component) = self.get_mut(type_path) {
+                    component.add_with_filter(layer_component, layer.weight, threshold, filter);
+                }
+            } else if let Some(component) = self.get_mut(type_path) {
+                component.blend_with_filter(layer_component, layer.weight, threshold, filter);
+            } else {
+                let mut component = BoundComponentValue(vec![]);
+                component.blend_with_filter(layer_component, layer.weight, threshold, filter);
+
+                if !component.0.is_empty() {
+                    self.insert(type_path.clone(), component);
+                }
+            }
+        }
+    }
+}
+
+///目标实体的动画求值器
+///
+///采样的缓冲区在帧之间复用，求值结果通过编译后的绑定直接写入组件
+#[derive(Component, Default)]
+pub struct NextAnimation {
+    //采样的动画资源，用于查找编译后的绑定
+    pub(crate) animations: AssetId<EntityAnimations>,
+    //这一帧的动画状态
+    pub(crate) pose: BoundAnimationPose,
+    //过渡中淡出动画的状态
+    pub(crate) from_pose: BoundAnimationPose,
+    //混合空间中除第一个动画外其他动画的状态
+    pub(crate) blend_poses: Vec<BoundAnimationPose>,
+    //动画层的状态
+    pub(crate) layer_poses: Vec<BoundAnimationPose>,
+    //这一帧需要重新采样
+    pub(crate) dirty: bool,
+}
+
+impl NextAnimation {
+    ///这一帧的动画状态
+    pub fn pose(&self) -> &BoundAnimationPose {
+        &self.pose
+    }
+
+    ///type_path 对应的组件是否有和上一次写入不同的值
+    pub fn has_changes(&self, type_path: &ShortTypePath, applied: &AppliedValues) -> bool {
+        self.pose.get(type_path).is_some_and(|component| {
+            component
+                .0
+                .iter()
+                .any(|bound_value| !applied.is_applied(bound_value))
+        })
+    }
+
+    ///把动画状态写入 type_path 对应的组件、资源或资产，无法解析的绑定在编译时已经报告过，直接跳过
+    ///
+    ///写入失败的绑定报告一次后标记为失败，之后跳过
+    ///
+    ///只写入和 applied 中记录的上一次写入不同的值，所有值都没有变化时不会触发组件的变化检测
+    pub fn apply_component(
+        &self,
+        type_path: &ShortTypePath,
+        mut component: impl DerefMut<Target = dyn Reflect>,
+        applied: &mut AppliedValues,
+        compiled: &CompiledEntityAnimations,
+        asset_server: &AssetServer,
+    ) {
+        let Some(component_value) = self.pose.get(type_path) else {
+            return;
+        };
+
+        let Some(compiled_component) = compiled.component(type_path) else {
+            return;
+        };
+
+        for bound_value in component_value.0.iter() {
+            if applied.is_applied(bound_value) {
+                continue;
+            }
+
+            let path = bound_value.binding.path.as_str();
+
+            let Some(compiled_value) = compiled_component.value(path) else {
+                continue;
+            };
+
+            match component.reflect_path_mut(&compiled_value.path) {
+                Ok(field) => {
+                    match (compiled_value.fns.apply)(&bound_value.value, field, asset_server) {
+                        Ok(()) => applied.set_applied(bound_value),
+                        Err(e) => {
+                            compiled_value.set_failed();
+                            warn!("apply {} error: {}", path, e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    compiled_value.set_failed();
+                    warn!("{} not found: {}", path, e);
+                }
+            }
+        }
+    }
+}
+
+///上一次写入一个组件、资源或资产的值，值没有变化时不再写入，避免触发变化检测
+#[derive(Default)]
+pub struct AppliedValues(Vec<BoundValue>);
+
+impl AppliedValues {
+    ///目标被其他系统修改后需要清空，之后的值全部重新写入
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    fn is_applied(&self, bound_value: &BoundValue) -> bool {
+        self.0
+            .iter()
+            .find(|value| value.binding.path == bound_value.binding.path)
+            .is_some_and(|value| value.value == bound_value.value)
+    }
+
+    fn set_applied(&mut self, bound_value: &BoundValue) {
+        match self
+            .0
+            .iter_mut()
+            .find(|value| value.binding.path == bound_value.binding.path)
+        {
+            Some(value) => value.value.clone_from(&bound_value.value),
+            None => self.0.push(bound_value.clone()),
+        }
+    }
+}
+
+///目标实体上次写入 T 组件的值，每个组件类型单独保存，写入不同组件的系统可以并行
+#[derive(Component, Deref, DerefMut)]
+pub struct AppliedAnimation<T> {
+    #[deref]
+    values: AppliedValues,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for AppliedAnimation<T> {
+    fn default() -> Self {
+        Self {
+            values: Default::default(),
+            marker: PhantomData,
+        }
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_blend_pose() {
+        use super::BoundAnimationPose;
+        use crate::core::ShortTypePath;
+        use crate::track::BoundComponentValue;
+        use crate::value::{AssetPath, BoundValue, TrackValue, ValueBinding};
+
+        let bound_value = |path: &str, value: TrackValue| BoundValue {
+            binding: ValueBinding {
+                path: path.to_owned(),
+                value_type: ShortTypePath::from_type_path::<f32>(),
+            },
+            value,
+            discrete: false,
+        };
+        let asset = |path: &str| {
+            TrackValue::Asset(AssetPath::new(
+                path,
+                ShortTypePath::from_type_path::<bool>(),
+            ))
+        };
+
+        let type_path = ShortTypePath::from_type_path::<bool>();
+
+        let mut from = BoundAnimationPose::default();
+        from.insert(
+            type_path.clone(),
+            BoundComponentValue(vec![
+                bound_value(".a", TrackValue::Number(0.0)),
+                bound_value(".b", asset("a.png")),
+                bound_value(".c", TrackValue::Number(1.0)),
+            ]),
+        );
+
+        let mut to = BoundAnimationPose::default();
+        to.insert(
+            type_path.clone(),
+            BoundComponentValue(vec![
+                bound_value(".a", TrackValue::Number(4.0)),
+                bound_value(".b", asset("b.png")),
+            ]),
+        );
+
+        let mut pose = from.clone();
+        pose.blend_with(&to, 0.25, 0.5);
+
+        let values = &pose.get(&type_path).unwrap().0;
+        assert_eq!(values[0].value, TrackValue::Number(1.0));
+        assert_eq!(values[1].value, asset("a.png"));
+        assert_eq!(values[2].value, TrackValue::Number(1.0));
+
+        let mut pose = from.clone();
+        pose.blend_with(&to, 0.25, 0.2);
+
+        let values = &pose.get(&type_path).unwrap().0;
+        assert_eq!(values[1].value, asset("b.png"));
+    }
+
+    #[test]
+    fn test_additive_layer() {
+        use super::{BoundAnimationPose, EntityAnimation};
+        use crate::core::ShortTypePath;
+        use crate::layer::AnimationLayer;
+        use crate::track::{
+            BoundComponentValue, ComponentTrack, InterpolationMode, Keyframe, Track,
+        };
+        use crate::value::{BoundValue, TrackValue, ValueBinding};
+
+        let binding = ValueBinding {
+            path: ".a".to_owned(),
+            value_type: ShortTypePath::from_type_path::<f32>(),
+        };
+        let type_path = ShortTypePath::from_type_path::<bool>();
+
+        let mut track = Track::new(binding.clone(), 1.0);
+        track.set_mode(InterpolationMode::Linear);
+        track.add_keyframe(Keyframe::new(0.0, TrackValue::Number(2.0)));
+        track.add_keyframe(Keyframe::new(1.0, TrackValue::Number(6.0)));
+        let mut component_track = ComponentTrack::default();
+        component_track.add_track(track);
+
+        let mut animation = EntityAnimation::default();
+        animation.tracks.insert(type_path.clone(), component_track);
+        animation.make_additive(0.0);
+        assert!(animation.additive);
+
+        let mut pose = BoundAnimationPose::default();
+        pose.insert(
+            type_path.clone(),
+            BoundComponentValue(vec![BoundValue {
+                binding,
+                value: TrackValue::Number(10.0),
+                discrete: false,
+            }]),
+        );
+
+        let layer = AnimationLayer::new("recoil").with_weight(0.5);
+        pose.apply_layer(&layer, animation.additive, &animation.sample_pose(0.5), 0.5);
+
+        let values = &pose.get(&type_path).unwrap().0;
+        assert_eq!(values[0].value, TrackValue::Number(11.0));
+    }
+
+    #[test]
+    fn test_sample_pose_into() {
+        use super::{BoundAnimationPose, EntityAnimation};
+        use crate::core::ShortTypePath;
+        use crate::track::{BoundComponentValue, ComponentTrack, Keyframe, Track};
+        use crate::value::{BoundValue, TrackValue, ValueBinding};
+
+        let binding = |path: &str| ValueBinding {
+            path: path.to_owned(),
+            value_type: ShortTypePath::from_type_path::<f32>(),
+        };
+        let type_path = ShortTypePath::from_type_path::<bool>();
+
+        let mut track = Track::new(binding(".a"), 1.0);
+        track.add_keyframe(Keyframe::new(0.0, TrackValue::Number(2.0)));
+        let mut component_track = ComponentTrack::default();
+        component_track.add_track(track);
+
+        let mut animation = EntityAnimation::default();
+        animation.tracks.insert(type_path.clone(), component_track);
+
+        let mut pose = BoundAnimationPose::default();
+        pose.insert(
+            type_path.clone(),
+            BoundComponentValue(vec![
+                BoundValue {
+                    binding: binding(".b"),
+                    value: TrackValue::Number(1.0),
+                    discrete: false,
+                },
+                BoundValue {
+                    binding: binding(".a"),
+                    value: TrackValue::Number(1.0),
+                    discrete: false,
+                },
+            ]),
+        );
+        pose.insert(
+            ShortTypePath::from_type_path::<usize>(),
+            BoundComponentValue(vec![]),
+        );
+
+        animation.sample_pose_into(0.5, &mut pose);
+
+        assert_eq!(pose.len(), 1);
+        let values = &pose.get(&type_path).unwrap().0;
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].value, TrackValue::Number(2.0));
+    }
+
+    #[test]
+    fn test_apply_unchanged() {
+        use super::{AppliedValues, EntityAnimation, NextAnimation};
+        use crate::assets::{CompiledEntityAnimations, EntityAnimations};
+        use crate::core::{AnimationName, ShortTypePath};
+        use crate::track::{AnimateComponentFns, ComponentTrack, Keyframe, Track};
+        use crate::value::{AnimateValueFns, TrackValue, ValueBinding};
+        use bevy::{prelude::*, reflect::TypeRegistry};
+
+        let mut track = Track::new(
+            ValueBinding {
+                path: ".translation".to_owned(),
+                value_type: ShortTypePath::from_type_path::<Vec3>(),
+            },
+            1.0,
+        );
+        track.add_keyframe(Keyframe::new(0.0, TrackValue::Vec3(Vec3::X)));
+        track.add_keyframe(Keyframe::new(0.5, TrackValue::Vec3(Vec3::Y)));
+        let mut component_track = ComponentTrack::default();
+        component_track.add_track(track);
+
+        let mut animation = EntityAnimation::default();
+        animation.tracks.insert(
+            ShortTypePath::from_type_path::<Transform>(),
+            component_track,
+        );
+
+        let mut animations = EntityAnimations::default();
+        animations.insert(AnimationName::new("idle"), animation.clone());
+
+        let mut registry = TypeRegistry::default();
+        registry.register::<Transform>();
+        registry.register::<Vec3>();
+        registry.register_type_data::<Transform, AnimateComponentFns>();
+        registry.register_type_data::<Vec3, AnimateValueFns>();
+        let compiled = CompiledEntityAnimations::compile(&animations, &registry);
+
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()));
+        let asset_server = app.world().resource::<AssetServer>().clone();
+        let world = app.world_mut();
+        let entity = world.spawn(Transform::default()).id();
+
+        let mut next_animation = NextAnimation::default();
+        let mut applied = AppliedValues::default();
+        let mut apply = |world: &mut World, time: f32| {
+            world.increment_change_tick();
+            animation.sample_pose_into(time, &mut next_animation.pose);
+            let component = world.get_mut::<Transform>(entity).unwrap();
+            next_animation.apply_component(
+                &ShortTypePath::from_type_path::<Transform>(),
+                component.map_unchanged(|component| component as &mut dyn Reflect),
+                &mut applied,
+                &compiled,
+                &asset_server,
+            );
+            world
+                .entity(entity)
+                .get_ref::<Transform>()
+                .unwrap()
+                .last_changed()
+        };
+
+        let first = apply(world, 0.0);
+        assert_eq!(world.get::<Transform>(entity).unwrap().translation, Vec3::X);
+
+        assert_eq!(apply(world, 0.25), first);
+
+        assert_ne!(apply(world, 0.5), first);
+        assert_eq!(world.get::<Transform>(entity).unwrap().translation, Vec3::Y);
+    }
+}
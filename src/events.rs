@@ -0,0 +1,27 @@
+use bevy::prelude::*;
+
+use crate::core::AnimationName;
+
+///动画播放结束，同时作为 Event 发送和作为观察者事件触发到播放器实体上
+#[derive(Debug, Clone, Event)]
+pub struct AnimationFinished {
+    pub player: Entity,
+    pub name: AnimationName,
+}
+
+///动画完成了一次循环，loop_count 为已完成的循环次数
+#[derive(Debug, Clone, Event)]
+pub struct AnimationLooped {
+    pub player: Entity,
+    pub name: AnimationName,
+    pub loop_count: u32,
+}
+
+///播放经过了动画中的标记
+#[derive(Debug, Clone, Event)]
+pub struct AnimationMarkerReached {
+    pub player: Entity,
+    pub target: Entity,
+    pub name: String,
+    pub payload: serde_json::Value,
+}
@@ -0,0 +1,204 @@
+use crate::{
+    assets::EntityAnimations, core::EntityPath, entity::NextAnimation, plugin::NextAnimationTarget,
+};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+///被目标的动画驱动的子实体
+#[derive(Debug, Component)]
+pub struct NextAnimationChild {
+    //轨道所在的目标实体
+    pub target: Entity,
+    pub path: EntityPath,
+}
+
+///目标的动画中子实体路径的解析结果，层级或动画变化时重新解析
+#[derive(Debug, Default, Component)]
+pub struct NextAnimationChildren {
+    entities: HashMap<EntityPath, Entity>,
+}
+
+impl NextAnimationChildren {
+    pub fn get(&self, path: &EntityPath) -> Option<Entity> {
+        self.entities.get(path).copied()
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities.values().copied()
+    }
+}
+
+///从 root 开始按路径上的 Name 逐级查找子实体
+pub fn find_child(
+    root: Entity,
+    path: &EntityPath,
+    children_q: &Query<&Children>,
+    name_q: &Query<&Name>,
+) -> Option<Entity> {
+    let mut entity = root;
+
+    for name in path.names() {
+        entity = children_q
+            .get(entity)
+            .ok()?
+            .iter()
+            .find(|child| {
+                name_q
+                    .get(**child)
+                    .is_ok_and(|child| child.as_str() == name)
+            })
+            .copied()?;
+    }
+
+    (entity != root).then_some(entity)
+}
+
+type ResolveTargetData<'a> = (
+    Entity,
+    Ref<'a, NextAnimationTarget>,
+    Ref<'a, Handle<EntityAnimations>>,
+    Option<&'a mut NextAnimationChildren>,
+);
+
+type HierarchyChanged = Or<(Changed<Children>, Changed<Name>)>;
+
+///在播放器实体下解析目标动画中的子实体路径，只在层级、名称或动画变化时重新解析
+#[allow(clippy::too_many_arguments)]
+pub fn resolve_animation_children(
+    mut commands: Commands,
+    mut target_q: Query<ResolveTargetData>,
+    hierarchy_q: Query<(), HierarchyChanged>,
+    mut removed_children: RemovedComponents<Children>,
+    mut removed_names: RemovedComponents<Name>,
+    mut asset_events: EventReader<AssetEvent<EntityAnimations>>,
+    children_q: Query<&Children>,
+    name_q: Query<&Name>,
+    animations: Res<Assets<EntityAnimations>>,
+    mut missing: Local<HashSet<(Entity, EntityPath)>>,
+) {
+    let changed = !hierarchy_q.is_empty()
+        | (removed_children.read().count() > 0)
+        | (removed_names.read().count() > 0)
+        | (asset_events.read().count() > 0);
+
+    for (entity, target, handle, children) in target_q.iter_mut() {
+        if !changed && !target.is_changed() && !handle.is_changed() && children.is_some() {
+            continue;
+        }
+
+        let mut entities: HashMap<EntityPath, Entity> = HashMap::default();
+
+        let paths = animations
+            .get(&*handle)
+            .into_iter()
+            .flat_map(|animations| animations.values())
+            .flat_map(|animation| animation.children.keys());
+
+        for path in paths {
+            if entities.contains_key(path) {
+                continue;
+            }
+
+            //无关的层级变化也会重新解析，每个目标的路径只在找不到时报告一次
+            match find_child(target.player, path, &children_q, &name_q) {
+                Some(child) => {
+                    missing.remove(&(entity, path.clone()));
+                    entities.insert(path.clone(), child);
+                }
+                None => {
+                    if missing.insert((entity, path.clone())) {
+                        warn!("{:?} child entity not found.", path);
+                    }
+                }
+            }
+        }
+
+        let old = children
+            .as_ref()
+            .map(|children| children.entities.clone())
+            .unwrap_or_default();
+
+        for (path, child) in old.iter() {
+            if entities.get(path) != Some(child) {
+                if let Some(mut child) = commands.get_entity(*child) {
+                    child.remove::<(NextAnimationChild, NextAnimation)>();
+                }
+            }
+        }
+
+        for (path, child) in entities.iter() {
+            if old.get(path) != Some(child) {
+                commands.entity(*child).insert((
+                    NextAnimationChild {
+                        target: entity,
+                        path: path.clone(),
+                    },
+                    NextAnimation::default(),
+                ));
+            }
+        }
+
+        match children {
+            Some(mut children) => children.entities = entities,
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(NextAnimationChildren { entities });
+            }
+        }
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_resolve_children() {
+        use super::{resolve_animation_children, NextAnimationChild, NextAnimationChildren};
+        use crate::{
+            assets::EntityAnimations,
+            core::{AnimationName, EntityPath},
+            entity::EntityAnimation,
+            plugin::NextAnimationTarget,
+        };
+        use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+        let mut world = World::new();
+        world.init_resource::<Events<AssetEvent<EntityAnimations>>>();
+        world.init_resource::<Assets<EntityAnimations>>();
+
+        let mut animation = EntityAnimation::default();
+        animation
+            .children
+            .insert(EntityPath::new("body/arm_r"), Default::default());
+
+        let mut animations = EntityAnimations::default();
+        animations.insert(AnimationName::new("attack"), animation);
+        let handle = world
+            .resource_mut::<Assets<EntityAnimations>>()
+            .add(animations);
+
+        let player = world.spawn_empty().id();
+        let arm = world.spawn(Name::new("arm_r")).id();
+        let body = world.spawn(Name::new("body")).add_child(arm).id();
+        world
+            .entity_mut(player)
+            .add_child(body)
+            .insert((NextAnimationTarget::new(player, "self"), handle));
+
+        world.run_system_once(resolve_animation_children);
+
+        let children = world.get::<NextAnimationChildren>(player).unwrap();
+        assert_eq!(children.get(&EntityPath::new("body/arm_r")), Some(arm));
+        assert_eq!(world.get::<NextAnimationChild>(arm).unwrap().target, player);
+
+        //改名后重新解析
+        world.entity_mut(arm).insert(Name::new("arm_l"));
+        world.run_system_once(resolve_animation_children);
+
+        let children = world.get::<NextAnimationChildren>(player).unwrap();
+        assert_eq!(children.get(&EntityPath::new("body/arm_r")), None);
+        assert!(world.get::<NextAnimationChild>(arm).is_none());
+    }
+}
@@ -0,0 +1,129 @@
+use bevy::utils::{HashMap, HashSet};
+
+use crate::core::{AnimationName, ShortTypePath};
+
+///动画层与下层动画的混合方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LayerBlendMode {
+    //按权重覆盖下层的值
+    #[default]
+    Override,
+    //在下层的值上叠加
+    Additive,
+}
+
+///动画层可以修改的组件和字段，为空时可以修改全部
+#[derive(Debug, Default, Clone)]
+pub struct AnimationMask {
+    components: HashSet<ShortTypePath>,
+    paths: HashMap<ShortTypePath, HashSet<String>>,
+}
+
+impl AnimationMask {
+    ///允许修改组件的所有字段
+    pub fn with_component(mut self, type_path: ShortTypePath) -> Self {
+        self.components.insert(type_path);
+        self
+    }
+
+    ///允许修改组件的 path 字段
+    pub fn with_path(mut self, type_path: ShortTypePath, path: &str) -> Self {
+        self.paths
+            .entry(type_path)
+            .or_default()
+            .insert(path.to_string());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty() && self.paths.is_empty()
+    }
+
+    pub fn allows(&self, type_path: &ShortTypePath, path: &str) -> bool {
+        self.is_empty()
+            || self.components.contains(type_path)
+            || self
+                .paths
+                .get(type_path)
+                .map(|paths| paths.contains(path))
+                .unwrap_or(false)
+    }
+}
+
+///叠加在播放器主动画之上的动画层
+#[derive(Debug, Clone)]
+pub struct AnimationLayer {
+    pub animation: AnimationName,
+    pub weight: f32,
+    pub blend_mode: LayerBlendMode,
+    pub mask: AnimationMask,
+    time: f32,
+}
+
+impl AnimationLayer {
+    pub fn new(animation_name: &str) -> Self {
+        Self {
+            animation: AnimationName::new(animation_name),
+            weight: 1.0,
+            blend_mode: LayerBlendMode::Override,
+            mask: AnimationMask::default(),
+            time: 0.0,
+        }
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: LayerBlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn with_mask(mut self, mask: AnimationMask) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    ///从头播放新的动画
+    pub fn play(&mut self, animation_name: &str) {
+        self.animation = AnimationName::new(animation_name);
+        self.time = 0.0;
+    }
+
+    pub fn seek(&mut self, seconds: f32) {
+        self.time = seconds;
+    }
+
+    pub fn elapsed(&self) -> f32 {
+        self.time
+    }
+
+    pub(crate) fn update(&mut self, dt: f32) {
+        self.time += dt;
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_mask() {
+        use super::AnimationMask;
+        use crate::core::ShortTypePath;
+
+        let legs = ShortTypePath::from_type_path::<bool>();
+        let body = ShortTypePath::from_type_path::<usize>();
+
+        assert!(AnimationMask::default().allows(&legs, ".a"));
+
+        let mask = AnimationMask::default()
+            .with_component(legs.clone())
+            .with_path(body.clone(), ".a");
+
+        assert!(mask.allows(&legs, ".b"));
+        assert!(mask.allows(&body, ".a"));
+        assert!(!mask.allows(&body, ".b"));
+        assert!(!mask.allows(&ShortTypePath::from_type_path::<f32>(), ".a"));
+    }
+}
@@ -1,17 +1,27 @@
 pub mod assets;
+pub mod blend_space;
 pub mod builder;
 pub mod core;
 pub mod entity;
+pub mod events;
+pub mod hierarchy;
+pub mod layer;
 pub mod plugin;
+pub mod state_machine;
 pub mod track;
 pub mod value;
 
 pub mod prelude {
     pub use crate::assets::*;
+    pub use crate::blend_space::*;
     pub use crate::builder::*;
     pub use crate::core::*;
     pub use crate::entity::*;
+    pub use crate::events::*;
+    pub use crate::hierarchy::*;
+    pub use crate::layer::*;
     pub use crate::plugin::*;
+    pub use crate::state_machine::*;
     pub use crate::track::*;
     pub use crate::value::*;
 }
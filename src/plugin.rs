@@ -1,16 +1,34 @@
 use crate::{
-    assets::EntityAnimationsLoader,
-    core::AnimationName,
-    entity::{EntityAnimationContext, NextAnimation},
+    assets::{
+        compile_animations, CompiledAnimations, CompiledEntityAnimations, EntityAnimationsLoader,
+    },
+    blend_space::{sample_blend_into, BlendClip},
+    builder::{collect_animation_targets, NextAnimationTargets},
+    core::{AnimationName, EntityPath, ShortTypePath},
+    entity::{AppliedAnimation, AppliedValues, EntityAnimation, NextAnimation},
+    events::{AnimationFinished, AnimationLooped, AnimationMarkerReached},
+    hierarchy::{resolve_animation_children, NextAnimationChild, NextAnimationChildren},
+    layer::AnimationLayer,
     prelude::EntityAnimations,
-    track::{AnimateComponent, AnimateComponentFns},
-    value::{AnimateValue, AnimateValueFns},
+    state_machine::{update_state_machines, AnimationStateMachine, AnimationStateMachineLoader},
+    track::{
+        AnimateAsset, AnimateAssetFns, AnimateComponent, AnimateComponentFns, AnimateResource,
+        AnimateResourceFns, Ease,
+    },
+    value::{AnimateValue, AnimateValueFns, ASSET_BLEND_THRESHOLD},
+};
+use bevy::{
+    asset::RecursiveDependencyLoadState,
+    prelude::*,
+    reflect::TypeData,
+    utils::{HashMap, HashSet},
 };
-use bevy::{ecs::system::SystemState, prelude::*};
 
 pub trait AnimationExt {
     fn register_animate_value<T: AnimateValue>(&mut self) -> &mut Self;
     fn register_animate_component<T: AnimateComponent>(&mut self) -> &mut Self;
+    fn register_animate_resource<R: AnimateResource>(&mut self) -> &mut Self;
+    fn register_animate_asset<A: AnimateAsset>(&mut self) -> &mut Self;
 }
 
 impl AnimationExt for App {
@@ -19,22 +37,150 @@ impl AnimationExt for App {
         self
     }
 
+    ///注册组件并添加写入该组件的系统，重复注册时不会重复添加
     fn register_animate_component<T: AnimateComponent>(&mut self) -> &mut Self {
+        if is_registered::<T, AnimateComponentFns>(self) {
+            return self;
+        }
+
         self.register_type_data::<T, AnimateComponentFns>();
+        self.add_systems(
+            PostUpdate,
+            apply_animations::<T>.in_set(AnimationSystems::Apply),
+        );
+        self
+    }
+
+    ///注册资源并添加写入该资源的系统，轨道的类型为资源的类型
+    fn register_animate_resource<R: AnimateResource>(&mut self) -> &mut Self {
+        if is_registered::<R, AnimateResourceFns>(self) {
+            return self;
+        }
+
+        self.register_type_data::<R, AnimateResourceFns>();
+        self.add_systems(
+            PostUpdate,
+            apply_resource_animations::<R>.in_set(AnimationSystems::Apply),
+        );
+        self
+    }
+
+    ///注册资产并添加写入该资产的系统，写入目标实体上 Handle 引用的资产
+    fn register_animate_asset<A: AnimateAsset>(&mut self) -> &mut Self {
+        if is_registered::<A, AnimateAssetFns>(self) {
+            return self;
+        }
+
+        self.register_type_data::<A, AnimateAssetFns>();
+        self.add_systems(
+            PostUpdate,
+            apply_asset_animations::<A>.in_set(AnimationSystems::Apply),
+        );
         self
     }
 }
 
+fn is_registered<T: 'static, D: TypeData>(app: &App) -> bool {
+    app.world()
+        .resource::<AppTypeRegistry>()
+        .read()
+        .get_type_data::<D>(std::any::TypeId::of::<T>())
+        .is_some()
+}
+
+///动画的系统集，按顺序执行
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AnimationSystems {
+    //推进播放器、状态机，发送事件
+    Advance,
+    //并行采样需要更新的目标
+    Sample,
+    //按组件类型并行写入
+    Apply,
+}
+
 #[derive(Debug, Component)]
 pub struct NextAnimationTarget {
     pub player: Entity,
+    //AnimationsBuilder 中的实体类别，同一个播放器的目标按类别分组
+    pub class: String,
 }
 
-#[derive(Default, Component)]
+impl NextAnimationTarget {
+    pub fn new(player: Entity, class: &str) -> Self {
+        Self {
+            player,
+            class: class.to_string(),
+        }
+    }
+}
+
+#[derive(Component)]
 pub struct NextAnimationPlayer {
     pub current_animation: AnimationName,
     time: f32,
+    //上一帧的播放时间，用于判断这一帧是否跨过了循环边界
+    last_time: f32,
     state: AnimationState,
+    //播放速度，负数时反向播放
+    speed: f32,
+    //等待解析的归一化跳转位置，需要动画时长才能换算为时间
+    pending_seek: Option<f32>,
+    //跳转后即使没有播放也需要更新一次动画
+    seeked: bool,
+    //正在进行的过渡
+    transition: Option<AnimationTransition>,
+    //过渡时无法插值的值(例如资源)在过渡权重达到该值时切换
+    transition_threshold: f32,
+    //按顺序叠加在主动画之上的动画层
+    layers: Vec<AnimationLayer>,
+    //混合空间中同步播放的动画，按权重从大到小排列，为空时只播放 current_animation
+    blend: Vec<BlendClip>,
+    //混合空间的归一化播放进度
+    blend_phase: f32,
+}
+
+impl Default for NextAnimationPlayer {
+    fn default() -> Self {
+        Self {
+            current_animation: Default::default(),
+            time: 0.0,
+            last_time: 0.0,
+            state: Default::default(),
+            speed: 1.0,
+            pending_seek: None,
+            seeked: false,
+            transition: None,
+            transition_threshold: ASSET_BLEND_THRESHOLD,
+            layers: vec![],
+            blend: vec![],
+            blend_phase: 0.0,
+        }
+    }
+}
+
+///从上一个动画淡出到当前动画的过渡
+#[derive(Debug, Clone)]
+pub struct AnimationTransition {
+    //淡出的动画
+    pub from: AnimationName,
+    //淡出动画的播放时间
+    from_time: f32,
+    //过渡已经经过的时间
+    elapsed: f32,
+    pub duration: f32,
+    pub curve: Ease,
+}
+
+impl AnimationTransition {
+    ///当前动画的权重，从 0 过渡到 1
+    pub fn weight(&self) -> f32 {
+        self.curve.sample(self.elapsed / self.duration)
+    }
+
+    pub fn from_time(&self) -> f32 {
+        self.from_time
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -42,97 +188,722 @@ pub enum AnimationState {
     #[default]
     Reset,
     Playing,
+    Paused,
     Stop,
 }
 
 impl NextAnimationPlayer {
     pub fn play(&mut self, animation_name: &str) {
+        self.transition = None;
+        self.start(animation_name);
+    }
+
+    ///在 duration 秒内按 curve 从当前动画过渡到新的动画
+    pub fn play_with_transition(&mut self, animation_name: &str, duration: f32, curve: Ease) {
+        if duration <= 0.0 || matches!(self.state, AnimationState::Reset) {
+            self.play(animation_name);
+            return;
+        }
+
+        self.transition = Some(AnimationTransition {
+            from: self.current_animation.clone(),
+            from_time: self.time,
+            elapsed: 0.0,
+            duration,
+            curve,
+        });
+        self.start(animation_name);
+    }
+
+    ///设置过渡时无法插值的值切换的位置，0 为过渡开始，1 为过渡结束
+    pub fn set_transition_threshold(&mut self, threshold: f32) {
+        self.transition_threshold = threshold.clamp(0.0, 1.0);
+    }
+
+    pub fn transition(&self) -> Option<&AnimationTransition> {
+        self.transition.as_ref()
+    }
+
+    ///添加动画层，返回层的索引
+    pub fn add_layer(&mut self, layer: AnimationLayer) -> usize {
+        self.layers.push(layer);
+        self.layers.len() - 1
+    }
+
+    pub fn remove_layer(&mut self, index: usize) -> Option<AnimationLayer> {
+        (index < self.layers.len()).then(|| self.layers.remove(index))
+    }
+
+    pub fn layer(&self, index: usize) -> Option<&AnimationLayer> {
+        self.layers.get(index)
+    }
+
+    pub fn layer_mut(&mut self, index: usize) -> Option<&mut AnimationLayer> {
+        self.layers.get_mut(index)
+    }
+
+    pub fn layers(&self) -> &[AnimationLayer] {
+        &self.layers
+    }
+
+    ///按权重同步播放多个动画，所有动画按相同的归一化进度采样
+    pub fn play_blend(&mut self, clips: Vec<BlendClip>) {
+        self.transition = None;
+        self.start_blend(clips);
+    }
+
+    ///在 duration 秒内按 curve 从当前动画过渡到混合的动画
+    pub fn play_blend_with_transition(
+        &mut self,
+        clips: Vec<BlendClip>,
+        duration: f32,
+        curve: Ease,
+    ) {
+        let Some(animation) = Self::heaviest(&clips) else {
+            return;
+        };
+
+        self.play_with_transition(&animation, duration, curve);
+        self.set_blend_clips(clips);
+    }
+
+    ///更新混合的权重，保持播放进度
+    pub fn set_blend_weights(&mut self, clips: Vec<BlendClip>) {
+        if let Some(animation) = Self::heaviest(&clips) {
+            self.current_animation = animation;
+        }
+
+        self.set_blend_clips(clips);
+    }
+
+    pub fn blend_clips(&self) -> &[BlendClip] {
+        &self.blend
+    }
+
+    ///混合空间的归一化播放进度
+    pub fn blend_phase(&self) -> f32 {
+        self.blend_phase
+    }
+
+    pub fn is_blending(&self) -> bool {
+        !self.blend.is_empty()
+    }
+
+    fn heaviest(clips: &[BlendClip]) -> Option<AnimationName> {
+        clips
+            .iter()
+            .max_by(|a, b| a.weight.total_cmp(&b.weight))
+            .map(|clip| clip.animation.clone())
+    }
+
+    fn start_blend(&mut self, clips: Vec<BlendClip>) {
+        let Some(animation) = Self::heaviest(&clips) else {
+            return;
+        };
+
+        self.start(&animation);
+        self.set_blend_clips(clips);
+    }
+
+    fn set_blend_clips(&mut self, mut clips: Vec<BlendClip>) {
+        for clip in clips.iter_mut() {
+            if let Some(old) = self
+                .blend
+                .iter()
+                .find(|old| old.animation == clip.animation)
+            {
+                clip.duration = old.duration;
+            }
+        }
+
+        clips.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+        self.blend = clips;
+    }
+
+    fn start(&mut self, animation_name: &str) {
+        self.blend.clear();
+        self.blend_phase = 0.0;
         self.current_animation = AnimationName::new(animation_name);
         self.state = AnimationState::Playing;
         self.time = 0.0;
+        self.last_time = 0.0;
+        self.pending_seek = None;
+    }
+
+    ///停止播放，保持当前的动画状态
+    pub fn stop(&mut self) {
+        self.state = AnimationState::Stop;
+    }
+
+    ///暂停播放，可以通过 resume 继续
+    pub fn pause(&mut self) {
+        if self.is_playing() {
+            self.state = AnimationState::Paused;
+        }
+    }
+
+    ///从暂停或停止的位置继续播放
+    pub fn resume(&mut self) {
+        if matches!(self.state, AnimationState::Paused | AnimationState::Stop) {
+            self.state = AnimationState::Playing;
+        }
     }
 
     pub fn is_playing(&self) -> bool {
         matches!(self.state, AnimationState::Playing)
     }
 
-    fn update(&mut self, dt: f32) {
-        self.time += dt;
+    pub fn is_paused(&self) -> bool {
+        matches!(self.state, AnimationState::Paused)
+    }
+
+    ///设置播放速度，负数时反向播放
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    ///跳转到 seconds 秒，跳过的标记不会触发
+    pub fn seek(&mut self, seconds: f32) {
+        self.time = seconds;
+        self.last_time = seconds;
+        self.pending_seek = None;
+        self.seeked = true;
+    }
+
+    ///按动画时长的比例跳转，0 为开头，1 为结尾
+    pub fn seek_normalized(&mut self, normalized: f32) {
+        self.pending_seek = Some(normalized.clamp(0.0, 1.0));
+        self.seeked = true;
     }
 
-    fn get_time(&self) -> f32 {
+    ///当前的播放时间(秒)
+    pub fn elapsed(&self) -> f32 {
         self.time
     }
+
+    fn update(&mut self, dt: f32) {
+        self.last_time = self.time;
+        self.time += dt * self.speed;
+
+        for layer in self.layers.iter_mut() {
+            layer.update(dt * self.speed);
+        }
+
+        //按权重平均的时长推进混合进度，使所有动画同步
+        let total = self.blend.iter().map(|clip| clip.weight).sum::<f32>();
+        let duration = self
+            .blend
+            .iter()
+            .map(|clip| clip.duration * clip.weight)
+            .sum::<f32>();
+
+        if total > 0.0 && duration > 0.0 {
+            self.blend_phase += dt * self.speed * total / duration;
+        }
+
+        if let Some(transition) = self.transition.as_mut() {
+            transition.from_time += dt * self.speed;
+            transition.elapsed += dt;
+
+            if transition.elapsed >= transition.duration {
+                self.transition = None;
+            }
+        }
+    }
 }
 
+///采样播放器当前的动画、过渡和动画层，写入目标的求值器，path 为子实体的路径
+fn sample_animation(
+    animation: &mut NextAnimation,
+    player: &NextAnimationPlayer,
+    animations: &EntityAnimations,
+    compiled: Option<&CompiledEntityAnimations>,
+    entity_animation: &EntityAnimation,
+    path: Option<&EntityPath>,
+) {
+    let threshold = player.transition_threshold;
+    let phase = player.blend_phase;
+
+    let sampled = player.is_blending()
+        && sample_blend_into(
+            &player.blend,
+            phase,
+            animations,
+            compiled,
+            path,
+            &mut animation.pose,
+            &mut animation.blend_poses,
+        );
+
+    if !sampled {
+        entity_animation.sample_path_into(path, player.elapsed(), compiled, &mut animation.pose);
+    }
+
+    if let Some(transition) = player.transition() {
+        if let Some(from_animation) = animations.get(&transition.from) {
+            from_animation.sample_path_into(
+                path,
+                transition.from_time(),
+                compiled,
+                &mut animation.from_pose,
+            );
+            animation
+                .pose
+                .blend_from(&animation.from_pose, transition.weight(), threshold);
+        }
+    }
+
+    animation
+        .layer_poses
+        .resize_with(player.layers().len(), Default::default);
+
+    for (layer, layer_pose) in player.layers().iter().zip(animation.layer_poses.iter_mut()) {
+        if let Some(layer_animation) = animations.get(&layer.animation) {
+            layer_animation.sample_path_into(path, layer.elapsed(), compiled, layer_pose);
+            animation
+                .pose
+                .apply_layer(layer, layer_animation.additive, layer_pose, threshold);
+        }
+    }
+}
+
+type AdvanceTargetData<'a> = (
+    &'a NextAnimationTarget,
+    &'a Handle<EntityAnimations>,
+    Option<&'a mut NextAnimation>,
+    Option<&'a NextAnimationChildren>,
+);
+
+///按播放器收集的目标推进播放，所有目标都结束时播放器才结束
+#[allow(clippy::too_many_arguments)]
 pub fn advance_animations(
     mut commands: Commands,
     time: Res<Time>,
-    mut player_q: Query<&mut NextAnimationPlayer>,
-    mut animation_target_q: Query<(
-        &NextAnimationTarget,
-        &Handle<EntityAnimations>,
-        Option<&mut NextAnimation>,
+    mut player_q: Query<(
         Entity,
+        &mut NextAnimationPlayer,
+        Option<&NextAnimationTargets>,
     )>,
+    mut animation_target_q: Query<AdvanceTargetData>,
+    mut child_q: Query<
+        &mut NextAnimation,
+        (With<NextAnimationChild>, Without<NextAnimationTarget>),
+    >,
+    handle_q: Query<&Handle<EntityAnimations>>,
     animations: Res<Assets<EntityAnimations>>,
-    registry: Res<AppTypeRegistry>,
     asset_server: Res<AssetServer>,
+    mut finished_events: EventWriter<AnimationFinished>,
+    mut looped_events: EventWriter<AnimationLooped>,
+    mut marker_events: EventWriter<AnimationMarkerReached>,
+    mut missing: Local<HashSet<(AssetId<EntityAnimations>, AnimationName)>>,
+    mut failed: Local<HashSet<AssetId<EntityAnimations>>>,
 ) {
     let dt = time.delta_seconds();
 
-    let registry = registry.read();
-
-    for (target, handle, animation, entity) in animation_target_q.iter_mut() {
-        if let Ok(mut player) = player_q.get_mut(target.player) {
-            if player.is_playing() {
-                player.update(dt);
-
-                let dt = player.get_time();
-
-                if let Some(new_anmation) = NextAnimation::new(
-                    &registry,
-                    &asset_server,
-                    &animations,
-                    handle,
-                    &player.current_animation,
-                    dt,
-                ) {
-                    if let Some(mut animation) = animation {
-                        *animation = new_anmation;
-                    } else {
-                        commands.entity(entity).insert(new_anmation);
+    for (player_entity, mut player, targets) in player_q.iter_mut() {
+        //关键帧引用的资源全部加载完成前不推进播放，直接添加的动画没有加载状态
+        //加载失败时仍然播放，关键帧会回退到通过 AssetServer 加载
+        let loaded = targets
+            .into_iter()
+            .flat_map(|targets| targets.iter())
+            .filter_map(|entity| animation_target_q.get(entity).ok())
+            .all(|(_, handle, ..)| {
+                match asset_server.get_recursive_dependency_load_state(handle) {
+                    None | Some(RecursiveDependencyLoadState::Loaded) => true,
+                    Some(RecursiveDependencyLoadState::Failed) => {
+                        if failed.insert(handle.id()) {
+                            warn!("{:?} dependencies failed to load.", handle.path());
+                        }
+                        true
                     }
-                } else {
-                    warn!("{:?} animation not found.", player.current_animation);
+                    _ => false,
                 }
+            });
+
+        if !loaded {
+            continue;
+        }
+
+        if player.is_playing() {
+            player.update(dt);
+        }
+
+        let Some(targets) = targets else {
+            continue;
+        };
+
+        if !player.is_playing() && !player.seeked {
+            continue;
+        }
+
+        if let Some(normalized) = player.pending_seek {
+            if let Some(duration) =
+                targets.duration(&player.current_animation, &handle_q, &animations)
+            {
+                player.seek(normalized * duration);
             }
-        } else {
-            warn!("{} player entity not found.", target.player);
+        }
+
+        let time = player.elapsed();
+
+        //所有目标是否都已播放结束
+        let mut finished: Option<bool> = None;
+        //这一帧跨过循环边界时的循环次数
+        let mut looped: Option<u32> = None;
+
+        for entity in targets.iter() {
+            let Ok((target, handle, animation, children)) = animation_target_q.get_mut(entity)
+            else {
+                continue;
+            };
+
+            let Some(target_animations) = animations.get(handle) else {
+                continue;
+            };
+
+            //该类别没有这个动画时保持当前的状态，每个类别只报告一次
+            let Some(entity_animation) = target_animations.get(&player.current_animation) else {
+                if missing.insert((handle.id(), player.current_animation.clone())) {
+                    warn!(
+                        "{:?} animation not found in {:?} class.",
+                        player.current_animation, target.class
+                    );
+                }
+                continue;
+            };
+
+            //混合的进度按动画时长推进，采样时只读取播放器
+            for clip in player.blend.iter_mut() {
+                if let Some(clip_animation) = target_animations.get(&clip.animation) {
+                    clip.duration = clip_animation.duration();
+                }
+            }
+
+            match animation {
+                Some(mut animation) => {
+                    animation.animations = handle.id();
+                    animation.dirty = true;
+                }
+                None => {
+                    commands.entity(entity).insert(NextAnimation {
+                        animations: handle.id(),
+                        dirty: true,
+                        ..Default::default()
+                    });
+                }
+            }
+
+            for child in children
+                .into_iter()
+                .flat_map(|children| children.entities())
+            {
+                if let Ok(mut animation) = child_q.get_mut(child) {
+                    animation.animations = handle.id();
+                    animation.dirty = true;
+                }
+            }
+
+            //混合空间按进度循环播放，不触发标记和结束事件
+            if player.is_blending() {
+                continue;
+            }
+
+            for marker in entity_animation.crossed_markers(player.last_time, time) {
+                let event = AnimationMarkerReached {
+                    player: player_entity,
+                    target: entity,
+                    name: marker.name.clone(),
+                    payload: marker.payload.clone(),
+                };
+
+                marker_events.send(event.clone());
+                commands.trigger_targets(event, player_entity);
+            }
+
+            if !player.is_playing() {
+                continue;
+            }
+
+            let is_finished = if player.speed() < 0.0 {
+                entity_animation.loop_mode.is_finite() && time <= 0.0
+            } else {
+                entity_animation.is_finished(time)
+            };
+
+            finished = Some(finished.unwrap_or(true) & is_finished);
+
+            let loop_count = entity_animation.loop_count(time);
+
+            if !is_finished && loop_count != entity_animation.loop_count(player.last_time) {
+                looped = Some(looped.unwrap_or_default().max(loop_count));
+            }
+        }
+
+        //目标还没有加载时保留按进度跳转，等时长确定后再处理
+        if player.pending_seek.is_none() {
+            player.seeked = false;
+        }
+
+        if let Some(loop_count) = looped {
+            let event = AnimationLooped {
+                player: player_entity,
+                name: player.current_animation.clone(),
+                loop_count,
+            };
+
+            looped_events.send(event.clone());
+            commands.trigger_targets(event, player_entity);
+        }
+
+        if finished == Some(true) {
+            player.stop();
+
+            let event = AnimationFinished {
+                player: player_entity,
+                name: player.current_animation.clone(),
+            };
+
+            finished_events.send(event.clone());
+            commands.trigger_targets(event, player_entity);
         }
     }
 }
 
-pub fn update_animations(world: &mut World) {
-    let mut state = SystemState::<Query<(Entity, &NextAnimation)>>::new(world);
+///并行采样这一帧需要更新的目标和子实体
+pub fn sample_animations(
+    mut animation_target_q: Query<(
+        &NextAnimationTarget,
+        &Handle<EntityAnimations>,
+        &mut NextAnimation,
+    )>,
+    mut child_q: Query<(&NextAnimationChild, &mut NextAnimation), Without<NextAnimationTarget>>,
+    target_q: Query<(&NextAnimationTarget, &Handle<EntityAnimations>)>,
+    player_q: Query<&NextAnimationPlayer>,
+    animations: Res<Assets<EntityAnimations>>,
+    compiled: Res<CompiledAnimations>,
+) {
+    let sample = |animation: &mut NextAnimation,
+                  target: &NextAnimationTarget,
+                  handle: &Handle<EntityAnimations>,
+                  path: Option<&EntityPath>| {
+        if !animation.dirty {
+            return;
+        }
+
+        animation.dirty = false;
 
-    let mut animations = vec![];
+        let Ok(player) = player_q.get(target.player) else {
+            return;
+        };
+
+        let Some(animations) = animations.get(handle) else {
+            return;
+        };
 
-    let animation_q = state.get(world);
+        let compiled = compiled.get(&handle.id());
+
+        if let Some(entity_animation) = animations.get(&player.current_animation) {
+            sample_animation(
+                animation,
+                player,
+                animations,
+                compiled,
+                entity_animation,
+                path,
+            );
+        }
+    };
+
+    animation_target_q
+        .par_iter_mut()
+        .for_each(|(target, handle, mut animation)| {
+            sample(&mut animation, target, handle, None);
+        });
+
+    child_q.par_iter_mut().for_each(|(child, mut animation)| {
+        if let Ok((target, handle)) = target_q.get(child.target) {
+            sample(&mut animation, target, handle, Some(&child.path));
+        }
+    });
+}
+
+///把采样结果写入 T 类型的组件，每个注册的组件类型一个系统
+pub fn apply_animations<T: AnimateComponent>(
+    mut animation_q: Query<(
+        Entity,
+        &mut T,
+        &NextAnimation,
+        Option<&mut AppliedAnimation<T>>,
+    )>,
+    commands: ParallelCommands,
+    compiled: Res<CompiledAnimations>,
+    asset_server: Res<AssetServer>,
+    mut type_path: Local<ShortTypePath>,
+) {
+    if type_path.is_empty() {
+        *type_path = ShortTypePath::from_type_path::<T>();
+    }
+
+    animation_q
+        .par_iter_mut()
+        .for_each(|(entity, component, animation, applied)| {
+            let Some(compiled) = compiled.get(&animation.animations) else {
+                return;
+            };
+
+            //上次运行后被添加或者被其他系统修改过，记录的值已经失效
+            let changed = component.is_changed();
+            let component = component.map_unchanged(|component| component as &mut dyn Reflect);
+
+            match applied {
+                Some(mut applied) => {
+                    if changed {
+                        applied.clear();
+                    }
+
+                    animation.apply_component(
+                        &type_path,
+                        component,
+                        &mut applied,
+                        compiled,
+                        &asset_server,
+                    );
+                }
+                None => {
+                    let mut applied = AppliedAnimation::<T>::default();
+                    animation.apply_component(
+                        &type_path,
+                        component,
+                        &mut applied,
+                        compiled,
+                        &asset_server,
+                    );
+                    commands.command_scope(|mut commands| {
+                        commands.entity(entity).insert(applied);
+                    });
+                }
+            }
+        });
+}
+
+///把采样结果写入 R 类型的资源，多个目标写入同一个资源时后写入的生效
+pub fn apply_resource_animations<R: AnimateResource>(
+    resource: Option<ResMut<R>>,
+    animation_q: Query<(Entity, &NextAnimation)>,
+    compiled: Res<CompiledAnimations>,
+    asset_server: Res<AssetServer>,
+    mut type_path: Local<ShortTypePath>,
+    //每个目标上次写入资源的值
+    mut applied: Local<HashMap<Entity, AppliedValues>>,
+) {
+    let Some(mut resource) = resource else {
+        return;
+    };
+
+    if type_path.is_empty() {
+        *type_path = ShortTypePath::from_type_path::<R>();
+    }
+
+    //上次运行后被添加或者被其他系统修改过，记录的值已经失效
+    if resource.is_changed() {
+        applied.clear();
+    } else {
+        applied.retain(|entity, _| animation_q.contains(*entity));
+    }
 
     for (entity, animation) in animation_q.iter() {
-        animations.push((entity, animation.clone()));
+        let Some(compiled) = compiled.get(&animation.animations) else {
+            continue;
+        };
+
+        animation.apply_component(
+            &type_path,
+            resource
+                .reborrow()
+                .map_unchanged(|resource| resource as &mut dyn Reflect),
+            applied.entry(entity).or_default(),
+            compiled,
+            &asset_server,
+        );
     }
+}
 
-    for (entity, animation) in animations.into_iter() {
-        let world = world.get_entity_mut(entity).unwrap();
+///把采样结果写入目标实体上 Handle<A> 引用的资产，值没有变化时不会修改资产
+#[allow(clippy::too_many_arguments)]
+pub fn apply_asset_animations<A: AnimateAsset>(
+    assets: Option<ResMut<Assets<A>>>,
+    animation_q: Query<(Entity, Ref<Handle<A>>, &NextAnimation)>,
+    mut events: EventReader<AssetEvent<A>>,
+    compiled: Res<CompiledAnimations>,
+    asset_server: Res<AssetServer>,
+    mut type_path: Local<ShortTypePath>,
+    //每个目标上次写入资产的值
+    mut applied: Local<HashMap<Entity, AppliedValues>>,
+    //这个系统修改资产产生的 Modified 事件数量，读到时抵消
+    mut written: Local<HashMap<AssetId<A>, usize>>,
+) {
+    //被添加、重新加载或者被其他系统修改过的资产，记录的值已经失效
+    let mut changed: HashSet<AssetId<A>> = HashSet::default();
 
-        let context = EntityAnimationContext {
-            animation,
-            entity_world: world,
+    for event in events.read() {
+        match event {
+            AssetEvent::Modified { id } => match written.get_mut(id) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => {
+                    changed.insert(*id);
+                }
+            },
+            AssetEvent::Added { id } | AssetEvent::LoadedWithDependencies { id } => {
+                changed.insert(*id);
+            }
+            AssetEvent::Removed { id } | AssetEvent::Unused { id } => {
+                written.remove(id);
+                changed.insert(*id);
+            }
+        }
+    }
+
+    let Some(mut assets) = assets else {
+        return;
+    };
+
+    if type_path.is_empty() {
+        *type_path = ShortTypePath::from_type_path::<A>();
+    }
+
+    applied.retain(|entity, _| animation_q.contains(*entity));
+
+    for (entity, handle, animation) in animation_q.iter() {
+        let applied = applied.entry(entity).or_default();
+
+        if handle.is_changed() || changed.contains(&handle.id()) {
+            applied.clear();
+        }
+
+        if !animation.has_changes(&type_path, applied) {
+            continue;
+        }
+
+        let Some(compiled) = compiled.get(&animation.animations) else {
+            continue;
+        };
+
+        let Some(asset) = assets.get_mut(&*handle) else {
+            continue;
         };
+        *written.entry(handle.id()).or_default() += 1;
 
-        context.apply();
+        animation.apply_component(
+            &type_path,
+            asset as &mut dyn Reflect,
+            applied,
+            compiled,
+            &asset_server,
+        );
     }
 }
 
@@ -140,15 +911,791 @@ pub struct BevyNextAnimationPlugin;
 
 impl Plugin for BevyNextAnimationPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
+        app.configure_sets(
             PostUpdate,
-            (advance_animations, update_animations)
+            (
+                AnimationSystems::Advance,
+                AnimationSystems::Sample,
+                AnimationSystems::Apply,
+            )
                 .chain()
                 .before(TransformSystem::TransformPropagate),
         );
-        app.init_asset::<EntityAnimations>()
+        app.add_systems(
+            PostUpdate,
+            (
+                (
+                    compile_animations,
+                    collect_animation_targets,
+                    update_state_machines,
+                    resolve_animation_children,
+                    advance_animations,
+                )
+                    .chain()
+                    .in_set(AnimationSystems::Advance),
+                sample_animations.in_set(AnimationSystems::Sample),
+            ),
+        );
+        app.add_event::<AnimationFinished>()
+            .add_event::<AnimationLooped>()
+            .add_event::<AnimationMarkerReached>();
+        app.init_resource::<CompiledAnimations>()
+            .init_asset::<EntityAnimations>()
             .init_asset_loader::<EntityAnimationsLoader>()
+            .init_asset::<AnimationStateMachine>()
+            .init_asset_loader::<AnimationStateMachineLoader>()
+            .register_type::<Vec2>()
+            .register_type::<Vec3>()
+            .register_type::<Vec4>()
+            .register_type::<Quat>()
+            .register_type::<Color>()
+            .register_type::<Val>()
+            .register_type::<Visibility>()
+            .register_type::<Handle<TextureAtlasLayout>>()
             .register_animate_value::<bool>()
-            .register_animate_value::<usize>();
+            .register_animate_value::<u8>()
+            .register_animate_value::<u16>()
+            .register_animate_value::<u32>()
+            .register_animate_value::<u64>()
+            .register_animate_value::<u128>()
+            .register_animate_value::<usize>()
+            .register_animate_value::<i8>()
+            .register_animate_value::<i16>()
+            .register_animate_value::<i32>()
+            .register_animate_value::<i64>()
+            .register_animate_value::<i128>()
+            .register_animate_value::<isize>()
+            .register_animate_value::<f32>()
+            .register_animate_value::<f64>()
+            .register_animate_value::<Vec2>()
+            .register_animate_value::<Vec3>()
+            .register_animate_value::<Vec4>()
+            .register_animate_value::<Quat>()
+            .register_animate_value::<Color>()
+            .register_animate_value::<Val>()
+            .register_animate_value::<Visibility>()
+            .register_animate_value::<Handle<TextureAtlasLayout>>();
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_player_playback_control() {
+        use super::NextAnimationPlayer;
+
+        let mut player = NextAnimationPlayer::default();
+        player.play("idle");
+
+        player.update(0.5);
+        assert_eq!(player.elapsed(), 0.5);
+
+        player.set_speed(2.0);
+        player.update(0.5);
+        assert_eq!(player.elapsed(), 1.5);
+
+        player.set_speed(-1.0);
+        player.update(0.5);
+        assert_eq!(player.elapsed(), 1.0);
+
+        player.pause();
+        assert!(player.is_paused());
+        assert!(!player.is_playing());
+
+        player.resume();
+        assert!(player.is_playing());
+
+        player.seek(3.0);
+        assert_eq!(player.elapsed(), 3.0);
+        assert_eq!(player.last_time, 3.0);
+
+        player.seek_normalized(2.0);
+        assert_eq!(player.pending_seek, Some(1.0));
+
+        player.play("run");
+        assert_eq!(player.elapsed(), 0.0);
+        assert_eq!(player.pending_seek, None);
+    }
+
+    #[test]
+    fn test_player_transition() {
+        use super::NextAnimationPlayer;
+        use crate::core::AnimationName;
+        use crate::track::Ease;
+
+        let mut player = NextAnimationPlayer::default();
+        player.play("idle");
+        player.update(0.5);
+
+        player.play_with_transition("run", 1.0, Ease::Linear);
+        assert_eq!(player.current_animation, AnimationName::new("run"));
+        assert_eq!(player.elapsed(), 0.0);
+
+        let transition = player.transition().unwrap();
+        assert_eq!(transition.from, AnimationName::new("idle"));
+        assert_eq!(transition.from_time(), 0.5);
+        assert_eq!(transition.weight(), 0.0);
+
+        player.update(0.25);
+        let transition = player.transition().unwrap();
+        assert_eq!(transition.from_time(), 0.75);
+        assert_eq!(transition.weight(), 0.25);
+
+        player.update(1.0);
+        assert!(player.transition().is_none());
+
+        player.play_with_transition("idle", 1.0, Ease::Linear);
+        player.play("run");
+        assert!(player.transition().is_none());
+    }
+
+    #[test]
+    fn test_animate_resource_and_asset() {
+        use super::{AnimationExt, BevyNextAnimationPlugin, NextAnimationPlayer};
+        use crate::assets::EntityAnimations;
+        use crate::builder::AnimationsBuilder;
+        use crate::core::{AnimationName, ShortTypePath};
+        use crate::entity::EntityAnimation;
+        use crate::track::{ComponentTrack, Keyframe, Track};
+        use crate::value::{TrackValue, ValueBinding};
+        use bevy::prelude::*;
+
+        #[derive(Resource, Reflect, Default)]
+        struct TimeOfDay {
+            sun: Vec2,
+        }
+
+        #[derive(Asset, Reflect, Default)]
+        struct TestMaterial {
+            color: Vec2,
+        }
+
+        fn component_track(path: &str) -> ComponentTrack {
+            let mut track = Track::new(
+                ValueBinding {
+                    path: path.to_owned(),
+                    value_type: ShortTypePath::from_type_path::<Vec2>(),
+                },
+                1.0,
+            );
+            track.add_keyframe(Keyframe::new(0.0, TrackValue::Vec2(Vec2::ONE)));
+            let mut component_track = ComponentTrack::default();
+            component_track.add_track(track);
+            component_track
+        }
+
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            BevyNextAnimationPlugin,
+        ))
+        .init_asset::<TestMaterial>()
+        .init_resource::<TimeOfDay>()
+        .register_type::<TimeOfDay>()
+        .register_type::<TestMaterial>()
+        .register_animate_resource::<TimeOfDay>()
+        .register_animate_asset::<TestMaterial>();
+
+        let mut animation = EntityAnimation::default();
+        animation.tracks.insert(
+            ShortTypePath::from_type_path::<TimeOfDay>(),
+            component_track(".sun"),
+        );
+        animation.tracks.insert(
+            ShortTypePath::from_type_path::<TestMaterial>(),
+            component_track(".color"),
+        );
+
+        let mut animations = EntityAnimations::default();
+        animations.insert(AnimationName::new("idle"), animation);
+
+        let world = app.world_mut();
+        let handle = world
+            .resource_mut::<Assets<EntityAnimations>>()
+            .add(animations);
+        let material = world
+            .resource_mut::<Assets<TestMaterial>>()
+            .add(TestMaterial::default());
+
+        let entity = world.spawn(material.clone()).id();
+        let mut builder = AnimationsBuilder::entity(entity);
+        builder.add_handle("self", handle);
+
+        let mut player = NextAnimationPlayer::default();
+        player.play("idle");
+        world
+            .entity_mut(entity)
+            .insert((player, builder.get_animation_bundle("self").unwrap()));
+
+        app.update();
+        app.update();
+
+        assert_eq!(app.world().resource::<TimeOfDay>().sun, Vec2::ONE);
+        assert_eq!(
+            app.world()
+                .resource::<Assets<TestMaterial>>()
+                .get(&material)
+                .unwrap()
+                .color,
+            Vec2::ONE
+        );
+
+        //被其他系统修改后重新写入
+        app.world_mut().resource_mut::<TimeOfDay>().sun = Vec2::ZERO;
+        app.world_mut()
+            .resource_mut::<Assets<TestMaterial>>()
+            .get_mut(&material)
+            .unwrap()
+            .color = Vec2::ZERO;
+        app.update();
+        app.update();
+
+        assert_eq!(app.world().resource::<TimeOfDay>().sun, Vec2::ONE);
+        assert_eq!(
+            app.world()
+                .resource::<Assets<TestMaterial>>()
+                .get(&material)
+                .unwrap()
+                .color,
+            Vec2::ONE
+        );
+    }
+
+    #[test]
+    fn test_apply_components() {
+        use super::{AnimationExt, BevyNextAnimationPlugin, NextAnimationPlayer};
+        use crate::assets::EntityAnimations;
+        use crate::builder::AnimationsBuilder;
+        use crate::core::{AnimationName, ShortTypePath};
+        use crate::entity::{AppliedAnimation, EntityAnimation};
+        use crate::track::{ComponentTrack, Keyframe, Track};
+        use crate::value::{TrackValue, ValueBinding};
+        use bevy::prelude::*;
+
+        #[derive(Component, Reflect, Default)]
+        struct TestAlpha {
+            alpha: f32,
+        }
+
+        #[derive(Component, Reflect, Default)]
+        struct TestOffset {
+            offset: Vec2,
+        }
+
+        fn component_track<T: TypePath>(path: &str, value: TrackValue) -> ComponentTrack {
+            let mut track = Track::new(
+                ValueBinding {
+                    path: path.to_owned(),
+                    value_type: ShortTypePath::from_type_path::<T>(),
+                },
+                1.0,
+            );
+            track.add_keyframe(Keyframe::new(0.0, value));
+            let mut component_track = ComponentTrack::default();
+            component_track.add_track(track);
+            component_track
+        }
+
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            BevyNextAnimationPlugin,
+        ))
+        .register_type::<TestAlpha>()
+        .register_type::<TestOffset>()
+        .register_animate_component::<TestAlpha>()
+        .register_animate_component::<TestOffset>();
+
+        let mut animation = EntityAnimation::default();
+        animation.tracks.insert(
+            ShortTypePath::from_type_path::<TestAlpha>(),
+            component_track::<f32>(".alpha", TrackValue::Number(0.5)),
+        );
+        animation.tracks.insert(
+            ShortTypePath::from_type_path::<TestOffset>(),
+            component_track::<Vec2>(".offset", TrackValue::Vec2(Vec2::ONE)),
+        );
+
+        let mut animations = EntityAnimations::default();
+        animations.insert(AnimationName::new("idle"), animation);
+
+        let world = app.world_mut();
+        let handle = world
+            .resource_mut::<Assets<EntityAnimations>>()
+            .add(animations);
+
+        let entity = world
+            .spawn((TestAlpha::default(), TestOffset::default()))
+            .id();
+        let mut builder = AnimationsBuilder::entity(entity);
+        builder.add_handle("self", handle);
+
+        let mut player = NextAnimationPlayer::default();
+        player.play("idle");
+        world
+            .entity_mut(entity)
+            .insert((player, builder.get_animation_bundle("self").unwrap()));
+
+        app.update();
+        app.update();
+
+        let world = app.world();
+        assert_eq!(world.get::<TestAlpha>(entity).unwrap().alpha, 0.5);
+        assert_eq!(world.get::<TestOffset>(entity).unwrap().offset, Vec2::ONE);
+
+        //每个组件类型保存各自写入的值
+        assert!(world.get::<AppliedAnimation<TestAlpha>>(entity).is_some());
+        assert!(world.get::<AppliedAnimation<TestOffset>>(entity).is_some());
+
+        //写入组件的系统不是独占系统，可以并行执行
+        let schedule = world.resource::<Schedules>().get(PostUpdate).unwrap();
+        let apply_systems = schedule
+            .systems()
+            .unwrap()
+            .filter(|(_, system)| system.name().contains("apply_animations"))
+            .map(|(_, system)| system.is_exclusive())
+            .collect::<Vec<_>>();
+        assert_eq!(apply_systems, vec![false, false]);
+    }
+
+    #[test]
+    fn test_animation_events() {
+        use super::{AnimationExt, BevyNextAnimationPlugin, NextAnimationPlayer};
+        use crate::assets::EntityAnimations;
+        use crate::builder::AnimationsBuilder;
+        use crate::core::{AnimationName, ShortTypePath};
+        use crate::entity::EntityAnimation;
+        use crate::events::{AnimationFinished, AnimationLooped, AnimationMarkerReached};
+        use crate::track::{ComponentTrack, Keyframe, LoopMode, Marker, Track};
+        use crate::value::{TrackValue, ValueBinding};
+        use bevy::prelude::*;
+        use bevy::time::TimeUpdateStrategy;
+        use std::time::Duration;
+
+        #[derive(Component, Reflect, Default)]
+        struct TestAlpha {
+            alpha: f32,
+        }
+
+        //发送的事件和触发到播放器上的事件
+        #[derive(Resource, Default)]
+        struct Received {
+            sent: Vec<String>,
+            triggered: Vec<String>,
+        }
+
+        fn read_events(
+            mut received: ResMut<Received>,
+            mut finished: EventReader<AnimationFinished>,
+            mut looped: EventReader<AnimationLooped>,
+            mut markers: EventReader<AnimationMarkerReached>,
+        ) {
+            for event in markers.read() {
+                received.sent.push(event.name.clone());
+            }
+            for event in looped.read() {
+                received.sent.push(format!("looped {}", event.loop_count));
+            }
+            for event in finished.read() {
+                received.sent.push(format!("finished {:?}", event.name));
+            }
+        }
+
+        let animation = |duration: f32, loop_mode: LoopMode| {
+            let mut track = Track::new(
+                ValueBinding {
+                    path: ".alpha".to_owned(),
+                    value_type: ShortTypePath::from_type_path::<f32>(),
+                },
+                duration,
+            );
+            track.add_keyframe(Keyframe::new(0.0, TrackValue::Number(1.0)));
+            let mut component_track = ComponentTrack::default();
+            component_track.add_track(track);
+
+            let mut animation = EntityAnimation {
+                loop_mode,
+                ..Default::default()
+            };
+            animation.tracks.insert(
+                ShortTypePath::from_type_path::<TestAlpha>(),
+                component_track,
+            );
+            animation
+        };
+
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            BevyNextAnimationPlugin,
+        ))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            200,
+        )))
+        .init_resource::<Received>()
+        .register_type::<TestAlpha>()
+        .register_animate_component::<TestAlpha>()
+        .add_systems(Last, read_events);
+
+        let mut walk = animation(1.0, LoopMode::Loop);
+        walk.markers.add_marker(Marker::new("step", 0.5));
+
+        let mut animations = EntityAnimations::default();
+        animations.insert(AnimationName::new("walk"), walk);
+        animations.insert(AnimationName::new("attack"), animation(0.5, LoopMode::Once));
+
+        let world = app.world_mut();
+        let handle = world
+            .resource_mut::<Assets<EntityAnimations>>()
+            .add(animations);
+
+        let entity = world.spawn(TestAlpha::default()).id();
+        let mut builder = AnimationsBuilder::entity(entity);
+        builder.add_handle("self", handle);
+
+        let mut player = NextAnimationPlayer::default();
+        player.play("walk");
+        world
+            .entity_mut(entity)
+            .insert((player, builder.get_animation_bundle("self").unwrap()))
+            .observe(
+                |trigger: Trigger<AnimationMarkerReached>, mut received: ResMut<Received>| {
+                    received.triggered.push(trigger.event().name.clone());
+                },
+            )
+            .observe(
+                |trigger: Trigger<AnimationLooped>, mut received: ResMut<Received>| {
+                    let event = trigger.event();
+                    received
+                        .triggered
+                        .push(format!("looped {}", event.loop_count));
+                },
+            )
+            .observe(
+                |trigger: Trigger<AnimationFinished>, mut received: ResMut<Received>| {
+                    let event = trigger.event();
+                    received
+                        .triggered
+                        .push(format!("finished {:?}", event.name));
+                },
+            );
+
+        //每帧 0.2 秒，经过 0.5 秒的标记和 1.0 秒的循环边界，停在 1.5 秒之前
+        for _ in 0..8 {
+            app.update();
+        }
+
+        app.world_mut()
+            .get_mut::<NextAnimationPlayer>(entity)
+            .unwrap()
+            .play("attack");
+
+        for _ in 0..5 {
+            app.update();
+        }
+
+        let expected = vec![
+            "step".to_string(),
+            "looped 1".to_string(),
+            format!("finished {:?}", AnimationName::new("attack")),
+        ];
+
+        let received = app.world().resource::<Received>();
+        assert_eq!(received.sent, expected);
+        assert_eq!(received.triggered, expected);
+        assert!(!app
+            .world()
+            .get::<NextAnimationPlayer>(entity)
+            .unwrap()
+            .is_playing());
+    }
+
+    #[test]
+    fn test_load_builtin_values() {
+        use super::{AnimationExt, BevyNextAnimationPlugin, NextAnimationPlayer};
+        use crate::assets::EntityAnimations;
+        use crate::builder::AnimationsBuilder;
+        use crate::core::{AnimationName, ShortTypePath};
+        use crate::entity::EntityAnimation;
+        use crate::track::{ComponentTrack, Keyframe, Track};
+        use crate::value::{TrackValue, ValueBinding};
+        use bevy::prelude::*;
+
+        #[derive(Component, Reflect, Default)]
+        struct TestValues {
+            alpha: f32,
+            layer: i32,
+            count: u8,
+            visibility: Visibility,
+            width: Val,
+        }
+
+        fn track<T: TypePath>(path: &str, value: f32) -> Track {
+            let mut track = Track::new(
+                ValueBinding {
+                    path: path.to_owned(),
+                    value_type: ShortTypePath::from_type_path::<T>(),
+                },
+                1.0,
+            );
+            track.add_keyframe(Keyframe::new(0.0, TrackValue::Number(value)));
+            track
+        }
+
+        let mut component_track = ComponentTrack::default();
+        component_track.add_track(track::<f32>(".alpha", 0.5));
+        component_track.add_track(track::<i32>(".layer", -2.6));
+        component_track.add_track(track::<u8>(".count", 300.0));
+        component_track.add_track(track::<Visibility>(".visibility", 0.0));
+        component_track.add_track(track::<Val>(".width", 12.0));
+
+        let mut animation = EntityAnimation::default();
+        animation.tracks.insert(
+            ShortTypePath::from_type_path::<TestValues>(),
+            component_track,
+        );
+
+        let mut animations = EntityAnimations::default();
+        animations.insert(AnimationName::new("idle"), animation);
+
+        //通过 EntityAnimationsLoader 从文件加载
+        let dir = std::env::temp_dir().join(format!("next_animation_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("values.entity_animations.json"),
+            serde_json::to_string(&animations).unwrap(),
+        )
+        .unwrap();
+
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin {
+                file_path: dir.to_string_lossy().to_string(),
+                ..Default::default()
+            },
+            BevyNextAnimationPlugin,
+        ))
+        .register_type::<TestValues>()
+        .register_animate_component::<TestValues>();
+
+        let handle: Handle<EntityAnimations> = app
+            .world()
+            .resource::<AssetServer>()
+            .load("values.entity_animations.json");
+
+        let entity = app.world_mut().spawn(TestValues::default()).id();
+        let mut builder = AnimationsBuilder::entity(entity);
+        builder.add_handle("self", handle);
+
+        let mut player = NextAnimationPlayer::default();
+        player.play("idle");
+        app.world_mut()
+            .entity_mut(entity)
+            .insert((player, builder.get_animation_bundle("self").unwrap()));
+
+        for _ in 0..200 {
+            app.update();
+
+            if app.world().get::<TestValues>(entity).unwrap().alpha != 0.0 {
+                break;
+            }
+
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let values = app.world().get::<TestValues>(entity).unwrap();
+        assert_eq!(values.alpha, 0.5);
+        assert_eq!(values.layer, -3);
+        assert_eq!(values.count, 255);
+        assert_eq!(values.visibility, Visibility::Hidden);
+        assert_eq!(values.width, Val::Px(12.0));
+    }
+
+    #[test]
+    fn test_load_asset_dependency() {
+        use super::{AnimationExt, BevyNextAnimationPlugin, NextAnimationPlayer};
+        use crate::assets::EntityAnimations;
+        use crate::builder::AnimationsBuilder;
+        use crate::core::{AnimationName, ShortTypePath};
+        use crate::entity::EntityAnimation;
+        use crate::track::{ComponentTrack, Keyframe, Track};
+        use crate::value::{AssetPath, TrackValue, ValueBinding};
+        use bevy::asset::io::Reader;
+        use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext, UntypedHandle};
+        use bevy::prelude::*;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::Duration;
+
+        //依赖的资源在放行前一直处于加载中
+        static RELEASED: AtomicBool = AtomicBool::new(false);
+
+        #[derive(Asset, TypePath)]
+        struct TestText(#[allow(dead_code)] String);
+
+        #[derive(Default)]
+        struct TestTextLoader;
+
+        impl AssetLoader for TestTextLoader {
+            type Asset = TestText;
+            type Settings = ();
+            type Error = std::io::Error;
+            async fn load<'a>(
+                &'a self,
+                reader: &'a mut Reader<'_>,
+                _settings: &'a (),
+                _load_context: &'a mut LoadContext<'_>,
+            ) -> Result<TestText, std::io::Error> {
+                while !RELEASED.load(Ordering::Acquire) {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+
+                let mut text = String::new();
+                reader.read_to_string(&mut text).await?;
+                Ok(TestText(text))
+            }
+
+            fn extensions(&self) -> &[&str] {
+                &["test_text"]
+            }
+        }
+
+        #[derive(Component, Reflect, Default)]
+        struct TestLabel {
+            text: Handle<TestText>,
+        }
+
+        let type_path = ShortTypePath::from_type_path::<Handle<TestText>>();
+        let mut track = Track::new(
+            ValueBinding {
+                path: ".text".to_owned(),
+                value_type: type_path.clone(),
+            },
+            1.0,
+        );
+        track.add_keyframe(Keyframe::new(
+            0.0,
+            TrackValue::Asset(AssetPath::new("label.test_text", type_path)),
+        ));
+
+        let mut component_track = ComponentTrack::default();
+        component_track.add_track(track);
+
+        let mut animation = EntityAnimation::default();
+        animation.tracks.insert(
+            ShortTypePath::from_type_path::<TestLabel>(),
+            component_track,
+        );
+
+        let mut animations = EntityAnimations::default();
+        animations.insert(AnimationName::new("idle"), animation);
+
+        let dir = std::env::temp_dir().join(format!("next_animation_dep_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("label.test_text"), "label").unwrap();
+        std::fs::write(
+            dir.join("label.entity_animations.json"),
+            serde_json::to_string(&animations).unwrap(),
+        )
+        .unwrap();
+
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin {
+                file_path: dir.to_string_lossy().to_string(),
+                ..Default::default()
+            },
+            BevyNextAnimationPlugin,
+        ))
+        .init_asset::<TestText>()
+        .init_asset_loader::<TestTextLoader>()
+        .register_type::<TestLabel>()
+        .register_type::<Handle<TestText>>()
+        .register_animate_value::<Handle<TestText>>()
+        .register_animate_component::<TestLabel>();
+
+        let handle: Handle<EntityAnimations> = app
+            .world()
+            .resource::<AssetServer>()
+            .load("label.entity_animations.json");
+
+        let entity = app.world_mut().spawn(TestLabel::default()).id();
+        let mut builder = AnimationsBuilder::entity(entity);
+        builder.add_handle("self", handle.clone());
+
+        let mut player = NextAnimationPlayer::default();
+        player.play("idle");
+        app.world_mut()
+            .entity_mut(entity)
+            .insert((player, builder.get_animation_bundle("self").unwrap()));
+
+        for _ in 0..200 {
+            app.update();
+
+            if app
+                .world()
+                .resource::<Assets<EntityAnimations>>()
+                .contains(&handle)
+            {
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        //关键帧中保存了依赖的强引用
+        let animations = app.world().resource::<Assets<EntityAnimations>>();
+        let key_frame = &animations.get(&handle).unwrap()[&AnimationName::new("idle")].tracks
+            [&ShortTypePath::from_type_path::<TestLabel>()]
+            .values[".text"]
+            .frames
+            .keyframes()[0];
+        let TrackValue::Asset(asset) = &key_frame.value else {
+            panic!("keyframe is not an asset.");
+        };
+        assert!(matches!(asset.handle(), Some(UntypedHandle::Strong(_))));
+
+        //依赖加载完成前不推进播放，也不写入组件
+        for _ in 0..5 {
+            std::thread::sleep(Duration::from_millis(5));
+            app.update();
+        }
+
+        let player = app.world().get::<NextAnimationPlayer>(entity).unwrap();
+        assert_eq!(player.elapsed(), 0.0);
+        assert_eq!(
+            app.world().get::<TestLabel>(entity).unwrap().text,
+            Handle::default()
+        );
+
+        RELEASED.store(true, Ordering::Release);
+
+        for _ in 0..200 {
+            app.update();
+
+            if app.world().get::<TestLabel>(entity).unwrap().text != Handle::default() {
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let text = &app.world().get::<TestLabel>(entity).unwrap().text;
+        assert_eq!(text.path().unwrap().to_string(), "label.test_text");
+        assert!(
+            app.world()
+                .get::<NextAnimationPlayer>(entity)
+                .unwrap()
+                .elapsed()
+                > 0.0
+        );
     }
 }
@@ -0,0 +1,646 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    reflect::TypePath,
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    assets::EntityAnimations, blend_space::BlendSpace, builder::NextAnimationTargets,
+    core::AnimationName, plugin::NextAnimationPlayer, track::Ease,
+};
+
+///由参数驱动的动画状态机
+#[derive(Debug, Asset, TypePath, Clone, Deserialize, Serialize)]
+pub struct AnimationStateMachine {
+    //初始状态
+    pub initial: String,
+    pub states: HashMap<String, AnimationStateNode>,
+    //按顺序检查的转换，每帧最多触发一个
+    #[serde(default)]
+    pub transitions: Vec<StateMachineTransition>,
+}
+
+impl AnimationStateMachine {
+    pub fn new(initial: &str) -> Self {
+        Self {
+            initial: initial.to_string(),
+            states: Default::default(),
+            transitions: vec![],
+        }
+    }
+
+    pub fn add_state(&mut self, name: &str, animation_name: &str) {
+        self.states.insert(
+            name.to_string(),
+            AnimationStateNode {
+                animation: AnimationName::new(animation_name),
+                blend_space: None,
+            },
+        );
+    }
+
+    pub fn add_blend_state(&mut self, name: &str, blend_space: BlendSpace) {
+        self.states.insert(
+            name.to_string(),
+            AnimationStateNode {
+                animation: Default::default(),
+                blend_space: Some(blend_space),
+            },
+        );
+    }
+
+    pub fn add_transition(&mut self, transition: StateMachineTransition) {
+        self.transitions.push(transition);
+    }
+
+    ///从 state 出发，条件满足的第一个转换，动画时长未知时不检查退出时间
+    pub fn find_transition(
+        &self,
+        state: &str,
+        parameters: &AnimationParameters,
+        normalized_time: Option<f32>,
+    ) -> Option<&StateMachineTransition> {
+        self.transitions.iter().find(|transition| {
+            let from_state = match &transition.from {
+                Some(from) => from == state,
+                None => transition.to != state,
+            };
+
+            from_state
+                && transition
+                    .exit_time
+                    .map(|exit_time| normalized_time.is_some_and(|time| time >= exit_time))
+                    .unwrap_or(true)
+                && transition
+                    .conditions
+                    .iter()
+                    .all(|condition| condition.is_satisfied(parameters))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AnimationStateNode {
+    #[serde(default)]
+    pub animation: AnimationName,
+    //设置后按参数在多个动画之间混合，忽略 animation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blend_space: Option<BlendSpace>,
+}
+
+impl AnimationStateNode {
+    fn enter(
+        &self,
+        player: &mut NextAnimationPlayer,
+        parameters: &AnimationParameters,
+        duration: f32,
+        curve: Ease,
+    ) {
+        match &self.blend_space {
+            Some(blend_space) => {
+                player.play_blend_with_transition(blend_space.clips(parameters), duration, curve)
+            }
+            None => player.play_with_transition(&self.animation, duration, curve),
+        }
+    }
+}
+
+///状态之间的转换
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StateMachineTransition {
+    //为空时可以从任意状态转换
+    #[serde(default)]
+    pub from: Option<String>,
+    pub to: String,
+    #[serde(default)]
+    pub conditions: Vec<TransitionCondition>,
+    //当前动画播放到该比例(0 为开头，1 为结尾)后才能转换
+    #[serde(default)]
+    pub exit_time: Option<f32>,
+    //过渡时长(秒)
+    #[serde(default)]
+    pub duration: f32,
+    #[serde(default)]
+    pub curve: Ease,
+}
+
+impl StateMachineTransition {
+    pub fn new(from: Option<&str>, to: &str) -> Self {
+        Self {
+            from: from.map(|from| from.to_string()),
+            to: to.to_string(),
+            conditions: vec![],
+            exit_time: None,
+            duration: 0.0,
+            curve: Ease::Linear,
+        }
+    }
+
+    pub fn with_condition(mut self, condition: TransitionCondition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    pub fn with_exit_time(mut self, exit_time: f32) -> Self {
+        self.exit_time = Some(exit_time);
+        self
+    }
+
+    pub fn with_duration(mut self, duration: f32, curve: Ease) -> Self {
+        self.duration = duration;
+        self.curve = curve;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Compare {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Compare {
+    pub fn compare<T: PartialOrd>(&self, a: T, b: T) -> bool {
+        match self {
+            Compare::Greater => a > b,
+            Compare::GreaterOrEqual => a >= b,
+            Compare::Less => a < b,
+            Compare::LessOrEqual => a <= b,
+            Compare::Equal => a == b,
+            Compare::NotEqual => a != b,
+        }
+    }
+}
+
+///转换的条件，参数不存在或类型不匹配时不满足
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum TransitionCondition {
+    Float {
+        parameter: String,
+        compare: Compare,
+        value: f32,
+    },
+    Int {
+        parameter: String,
+        compare: Compare,
+        value: i32,
+    },
+    Bool {
+        parameter: String,
+        value: bool,
+    },
+    //触发后在转换时被消耗
+    Trigger {
+        parameter: String,
+    },
+}
+
+impl TransitionCondition {
+    pub fn is_satisfied(&self, parameters: &AnimationParameters) -> bool {
+        match self {
+            TransitionCondition::Float {
+                parameter,
+                compare,
+                value,
+            } => parameters
+                .get_float(parameter)
+                .map(|parameter| compare.compare(parameter, *value))
+                .unwrap_or(false),
+            TransitionCondition::Int {
+                parameter,
+                compare,
+                value,
+            } => parameters
+                .get_int(parameter)
+                .map(|parameter| compare.compare(parameter, *value))
+                .unwrap_or(false),
+            TransitionCondition::Bool { parameter, value } => {
+                parameters.get_bool(parameter) == Some(*value)
+            }
+            TransitionCondition::Trigger { parameter } => parameters.is_triggered(parameter),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParameterValue {
+    Float(f32),
+    Int(i32),
+    Bool(bool),
+    Trigger(bool),
+}
+
+///状态机使用的参数
+#[derive(Debug, Default, Clone, Component, Deref, DerefMut)]
+pub struct AnimationParameters(HashMap<String, ParameterValue>);
+
+impl AnimationParameters {
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.insert(name.to_string(), ParameterValue::Float(value));
+    }
+
+    pub fn set_int(&mut self, name: &str, value: i32) {
+        self.insert(name.to_string(), ParameterValue::Int(value));
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.insert(name.to_string(), ParameterValue::Bool(value));
+    }
+
+    ///设置触发器，触发器在状态转换时被消耗
+    pub fn set_trigger(&mut self, name: &str) {
+        self.insert(name.to_string(), ParameterValue::Trigger(true));
+    }
+
+    pub fn reset_trigger(&mut self, name: &str) {
+        self.insert(name.to_string(), ParameterValue::Trigger(false));
+    }
+
+    pub fn get_float(&self, name: &str) -> Option<f32> {
+        match self.get(name) {
+            Some(ParameterValue::Float(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_int(&self, name: &str) -> Option<i32> {
+        match self.get(name) {
+            Some(ParameterValue::Int(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        match self.get(name) {
+            Some(ParameterValue::Bool(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn is_triggered(&self, name: &str) -> bool {
+        matches!(self.get(name), Some(ParameterValue::Trigger(true)))
+    }
+}
+
+///驱动播放器的状态机，和 NextAnimationPlayer 放在同一个实体上
+#[derive(Debug, Component)]
+pub struct AnimationStateMachineController {
+    pub state_machine: Handle<AnimationStateMachine>,
+    //当前状态，为空时进入初始状态
+    state: Option<String>,
+}
+
+impl AnimationStateMachineController {
+    pub fn new(state_machine: Handle<AnimationStateMachine>) -> Self {
+        Self {
+            state_machine,
+            state: None,
+        }
+    }
+
+    pub fn state(&self) -> Option<&str> {
+        self.state.as_deref()
+    }
+}
+
+pub fn update_state_machines(
+    mut controller_q: Query<(
+        &mut AnimationStateMachineController,
+        &mut NextAnimationPlayer,
+        Option<&mut AnimationParameters>,
+        Option<&NextAnimationTargets>,
+    )>,
+    handle_q: Query<&Handle<EntityAnimations>>,
+    state_machines: Res<Assets<AnimationStateMachine>>,
+    animations: Res<Assets<EntityAnimations>>,
+) {
+    let empty_parameters = AnimationParameters::default();
+
+    for (mut controller, mut player, mut parameters, targets) in controller_q.iter_mut() {
+        let Some(state_machine) = state_machines.get(&controller.state_machine) else {
+            continue;
+        };
+
+        let Some(state) = controller.state.clone() else {
+            let Some(node) = state_machine.states.get(&state_machine.initial) else {
+                warn!("{} state not found.", state_machine.initial);
+                continue;
+            };
+
+            node.enter(
+                &mut player,
+                parameters.as_deref().unwrap_or(&empty_parameters),
+                0.0,
+                Ease::Linear,
+            );
+            controller.state = Some(state_machine.initial.clone());
+            continue;
+        };
+
+        if let Some(blend_space) = state_machine
+            .states
+            .get(&state)
+            .and_then(|node| node.blend_space.as_ref())
+        {
+            player.set_blend_weights(
+                blend_space.clips(parameters.as_deref().unwrap_or(&empty_parameters)),
+            );
+        }
+
+        //时长和结束判断一致，取包含当前动画的目标中最长的
+        let normalized_time = if player.is_blending() {
+            Some(player.blend_phase())
+        } else {
+            targets
+                .and_then(|targets| {
+                    targets.duration(&player.current_animation, &handle_q, &animations)
+                })
+                .map(|duration| {
+                    if duration > 0.0 {
+                        player.elapsed() / duration
+                    } else {
+                        1.0
+                    }
+                })
+        };
+
+        let Some(transition) = state_machine.find_transition(
+            &state,
+            parameters.as_deref().unwrap_or(&empty_parameters),
+            normalized_time,
+        ) else {
+            continue;
+        };
+
+        let Some(node) = state_machine.states.get(&transition.to) else {
+            warn!("{} state not found.", transition.to);
+            continue;
+        };
+
+        if let Some(parameters) = parameters.as_mut() {
+            for condition in transition.conditions.iter() {
+                if let TransitionCondition::Trigger { parameter } = condition {
+                    parameters.reset_trigger(parameter);
+                }
+            }
+        }
+
+        node.enter(
+            &mut player,
+            parameters.as_deref().unwrap_or(&empty_parameters),
+            transition.duration,
+            transition.curve,
+        );
+        controller.state = Some(transition.to.clone());
+    }
+}
+
+#[derive(Default)]
+pub struct AnimationStateMachineLoader;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum AnimationStateMachineLoaderError {
+    /// An [IO](std::io) Error
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not load asset: {0}")]
+    Serde(#[from] serde_json::Error),
+}
+
+impl AssetLoader for AnimationStateMachineLoader {
+    type Asset = AnimationStateMachine;
+    type Settings = ();
+    type Error = AnimationStateMachineLoaderError;
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let state_machine = serde_json::from_slice::<AnimationStateMachine>(&bytes)?;
+
+        Ok(state_machine)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["animation_state_machine.json"]
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_find_transition() {
+        use super::{
+            AnimationParameters, AnimationStateMachine, Compare, StateMachineTransition,
+            TransitionCondition,
+        };
+
+        let mut state_machine = AnimationStateMachine::new("idle");
+        state_machine.add_state("idle", "idle");
+        state_machine.add_state("run", "run");
+        state_machine.add_state("jump", "jump");
+        state_machine.add_transition(
+            StateMachineTransition::new(Some("idle"), "run").with_condition(
+                TransitionCondition::Float {
+                    parameter: "speed".to_string(),
+                    compare: Compare::Greater,
+                    value: 0.1,
+                },
+            ),
+        );
+        state_machine.add_transition(StateMachineTransition::new(None, "jump").with_condition(
+            TransitionCondition::Trigger {
+                parameter: "jump".to_string(),
+            },
+        ));
+        state_machine
+            .add_transition(StateMachineTransition::new(Some("jump"), "idle").with_exit_time(1.0));
+
+        let mut parameters = AnimationParameters::default();
+        assert!(state_machine
+            .find_transition("idle", &parameters, Some(0.0))
+            .is_none());
+
+        parameters.set_float("speed", 1.0);
+        let transition = state_machine.find_transition("idle", &parameters, Some(0.0));
+        assert_eq!(transition.map(|t| t.to.as_str()), Some("run"));
+
+        parameters.set_trigger("jump");
+        let transition = state_machine.find_transition("run", &parameters, Some(0.0));
+        assert_eq!(transition.map(|t| t.to.as_str()), Some("jump"));
+
+        parameters.reset_trigger("jump");
+        assert!(state_machine
+            .find_transition("jump", &parameters, Some(0.5))
+            .is_none());
+        let transition = state_machine.find_transition("jump", &parameters, Some(1.0));
+        assert_eq!(transition.map(|t| t.to.as_str()), Some("idle"));
+
+        //动画时长未知时不按退出时间转换
+        assert!(state_machine
+            .find_transition("jump", &parameters, None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_update_state_machines() {
+        use super::{
+            update_state_machines, AnimationParameters, AnimationStateMachine,
+            AnimationStateMachineController, StateMachineTransition, TransitionCondition,
+        };
+        use crate::assets::EntityAnimations;
+        use crate::builder::{collect_animation_targets, AnimationsBuilder};
+        use crate::core::{AnimationName, ShortTypePath};
+        use crate::entity::EntityAnimation;
+        use crate::plugin::NextAnimationPlayer;
+        use crate::track::{ComponentTrack, Track};
+        use crate::value::ValueBinding;
+        use bevy::{ecs::system::RunSystemOnce, prelude::*};
+
+        let mut state_machine = AnimationStateMachine::new("idle");
+        state_machine.add_state("idle", "idle");
+        state_machine.add_state("jump", "jump");
+        state_machine.add_transition(StateMachineTransition::new(None, "jump").with_condition(
+            TransitionCondition::Trigger {
+                parameter: "jump".to_string(),
+            },
+        ));
+        state_machine
+            .add_transition(StateMachineTransition::new(Some("jump"), "idle").with_exit_time(1.0));
+
+        let mut world = World::new();
+        world.init_resource::<Assets<AnimationStateMachine>>();
+        world.init_resource::<Assets<EntityAnimations>>();
+
+        let state_machine = world
+            .resource_mut::<Assets<AnimationStateMachine>>()
+            .add(state_machine);
+        //动画稍后才添加，时长未知
+        let animations = world
+            .resource::<Assets<EntityAnimations>>()
+            .reserve_handle();
+
+        let player = world
+            .spawn((
+                AnimationStateMachineController::new(state_machine),
+                NextAnimationPlayer::default(),
+                AnimationParameters::default(),
+            ))
+            .id();
+
+        let mut builder = AnimationsBuilder::entity(player);
+        builder.add_handle("body", animations.clone());
+        let bundle = builder.get_animation_bundle("body").unwrap();
+        world.spawn(bundle);
+        world.run_system_once(collect_animation_targets);
+
+        let state = |world: &World| {
+            world
+                .get::<AnimationStateMachineController>(player)
+                .unwrap()
+                .state()
+                .map(str::to_string)
+        };
+        let current = |world: &World| {
+            world
+                .get::<NextAnimationPlayer>(player)
+                .unwrap()
+                .current_animation
+                .clone()
+        };
+
+        //进入初始状态
+        world.run_system_once(update_state_machines);
+        assert_eq!(state(&world).as_deref(), Some("idle"));
+        assert_eq!(current(&world), AnimationName::new("idle"));
+
+        //触发器在转换后被消耗
+        world
+            .get_mut::<AnimationParameters>(player)
+            .unwrap()
+            .set_trigger("jump");
+        world.run_system_once(update_state_machines);
+        assert_eq!(state(&world).as_deref(), Some("jump"));
+        assert_eq!(current(&world), AnimationName::new("jump"));
+        assert!(!world
+            .get::<AnimationParameters>(player)
+            .unwrap()
+            .is_triggered("jump"));
+
+        //动画时长未知时不按退出时间转换
+        world
+            .get_mut::<NextAnimationPlayer>(player)
+            .unwrap()
+            .seek(2.0);
+        world.run_system_once(update_state_machines);
+        assert_eq!(state(&world).as_deref(), Some("jump"));
+
+        let mut animation = EntityAnimation::default();
+        let mut component_track = ComponentTrack::default();
+        component_track.add_track(Track::new(
+            ValueBinding {
+                path: ".translation".to_owned(),
+                value_type: ShortTypePath::from_type_path::<Vec3>(),
+            },
+            1.0,
+        ));
+        animation.tracks.insert(
+            ShortTypePath::from_type_path::<Transform>(),
+            component_track,
+        );
+        let mut entity_animations = EntityAnimations::default();
+        entity_animations.insert(AnimationName::new("jump"), animation);
+        world
+            .resource_mut::<Assets<EntityAnimations>>()
+            .insert(&animations, entity_animations);
+
+        world.run_system_once(update_state_machines);
+        assert_eq!(state(&world).as_deref(), Some("idle"));
+        assert_eq!(current(&world), AnimationName::new("idle"));
+    }
+
+    #[test]
+    fn test_deserialize_state_machine() {
+        use super::{AnimationStateMachine, TransitionCondition};
+
+        let state_machine: AnimationStateMachine = serde_json::from_str(
+            r#"{
+                "initial": "idle",
+                "states": {
+                    "idle": { "animation": "idle" },
+                    "run": { "animation": "run" }
+                },
+                "transitions": [
+                    {
+                        "from": "idle",
+                        "to": "run",
+                        "conditions": [{ "Bool": { "parameter": "running", "value": true } }],
+                        "duration": 0.2,
+                        "curve": "QuadOut"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(state_machine.transitions[0].duration, 0.2);
+        assert_eq!(
+            state_machine.transitions[0].conditions[0],
+            TransitionCondition::Bool {
+                parameter: "running".to_string(),
+                value: true
+            }
+        );
+    }
+}
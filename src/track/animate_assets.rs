@@ -0,0 +1,15 @@
+use bevy::{prelude::*, reflect::FromType};
+
+impl<A: AnimateAsset> FromType<A> for AnimateAssetFns {
+    fn from_type() -> Self {
+        AnimateAssetFns
+    }
+}
+
+///标记可以被动画写入的资产，写入由 apply_asset_animations 完成
+#[derive(Clone)]
+pub struct AnimateAssetFns;
+
+pub trait AnimateAsset: Reflect + Asset + Sized + TypePath {}
+
+impl<T: Reflect + Asset + Sized + TypePath> AnimateAsset for T {}
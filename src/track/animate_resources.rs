@@ -0,0 +1,15 @@
+use bevy::{prelude::*, reflect::FromType};
+
+impl<R: AnimateResource> FromType<R> for AnimateResourceFns {
+    fn from_type() -> Self {
+        AnimateResourceFns
+    }
+}
+
+///标记可以被动画写入的资源，写入由 apply_resource_animations 完成
+#[derive(Clone)]
+pub struct AnimateResourceFns;
+
+pub trait AnimateResource: Reflect + Resource + Sized + TypePath {}
+
+impl<T: Reflect + Resource + Sized + TypePath> AnimateResource for T {}
@@ -0,0 +1,37 @@
+use bevy::math::Vec2;
+
+///三次 Hermite 插值，m0 和 m1 为已乘以区间长度的切线
+pub(crate) fn hermite(p0: f32, m0: f32, p1: f32, m1: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    (2.0 * t3 - 3.0 * t2 + 1.0) * p0
+        + (t3 - 2.0 * t2 + t) * m0
+        + (-2.0 * t3 + 3.0 * t2) * p1
+        + (t3 - t2) * m1
+}
+
+fn bezier(a: f32, b: f32, c: f32, d: f32, s: f32) -> f32 {
+    let inv = 1.0 - s;
+
+    inv * inv * inv * a + 3.0 * inv * inv * s * b + 3.0 * inv * s * s * c + s * s * s * d
+}
+
+///二维三次贝塞尔曲线，x 轴为时间且单调，求 x 处对应的 y
+pub(crate) fn cubic_bezier(p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, x: f32) -> f32 {
+    let mut low = 0.0;
+    let mut high = 1.0;
+    let mut s = 0.5;
+
+    for _ in 0..32 {
+        s = (low + high) * 0.5;
+
+        if bezier(p0.x, p1.x, p2.x, p3.x, s) < x {
+            low = s;
+        } else {
+            high = s;
+        }
+    }
+
+    bezier(p0.y, p1.y, p2.y, p3.y, s)
+}
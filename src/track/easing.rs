@@ -0,0 +1,216 @@
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+///关键帧到下一个关键帧之间使用的缓动函数
+#[derive(Debug, Default, PartialEq, PartialOrd, Clone, Copy, Deserialize, Serialize)]
+pub enum Ease {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    ExpoIn,
+    ExpoOut,
+    ExpoInOut,
+    BackIn,
+    BackOut,
+    BackInOut,
+    ElasticIn,
+    ElasticOut,
+    ElasticInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut,
+    //将区间分为 n 段阶跃
+    Steps(usize),
+}
+
+const BACK_C1: f32 = 1.70158;
+const BACK_C2: f32 = BACK_C1 * 1.525;
+const BACK_C3: f32 = BACK_C1 + 1.0;
+const ELASTIC_C4: f32 = 2.0 * PI / 3.0;
+const ELASTIC_C5: f32 = 2.0 * PI / 4.5;
+
+fn bounce_out(t: f32) -> f32 {
+    const N1: f32 = 7.5625;
+    const D1: f32 = 2.75;
+
+    if t < 1.0 / D1 {
+        N1 * t * t
+    } else if t < 2.0 / D1 {
+        let t = t - 1.5 / D1;
+        N1 * t * t + 0.75
+    } else if t < 2.5 / D1 {
+        let t = t - 2.25 / D1;
+        N1 * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D1;
+        N1 * t * t + 0.984375
+    }
+}
+
+impl Ease {
+    ///将 0 到 1 的进度映射为缓动后的进度，端点保持为 0 和 1
+    pub fn sample(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+
+        match *self {
+            Ease::Linear => t,
+            Ease::QuadIn => t * t,
+            Ease::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Ease::QuadInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+            Ease::CubicIn => t * t * t,
+            Ease::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Ease::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Ease::SineIn => 1.0 - (t * PI / 2.0).cos(),
+            Ease::SineOut => (t * PI / 2.0).sin(),
+            Ease::SineInOut => -((PI * t).cos() - 1.0) / 2.0,
+            Ease::ExpoIn => {
+                if t == 0.0 {
+                    0.0
+                } else {
+                    2f32.powf(10.0 * t - 10.0)
+                }
+            }
+            Ease::ExpoOut => {
+                if t == 1.0 {
+                    1.0
+                } else {
+                    1.0 - 2f32.powf(-10.0 * t)
+                }
+            }
+            Ease::ExpoInOut => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else if t < 0.5 {
+                    2f32.powf(20.0 * t - 10.0) / 2.0
+                } else {
+                    (2.0 - 2f32.powf(-20.0 * t + 10.0)) / 2.0
+                }
+            }
+            Ease::BackIn => BACK_C3 * t * t * t - BACK_C1 * t * t,
+            Ease::BackOut => 1.0 + BACK_C3 * (t - 1.0).powi(3) + BACK_C1 * (t - 1.0).powi(2),
+            Ease::BackInOut => {
+                if t < 0.5 {
+                    ((2.0 * t).powi(2) * ((BACK_C2 + 1.0) * 2.0 * t - BACK_C2)) / 2.0
+                } else {
+                    ((2.0 * t - 2.0).powi(2) * ((BACK_C2 + 1.0) * (t * 2.0 - 2.0) + BACK_C2) + 2.0)
+                        / 2.0
+                }
+            }
+            Ease::ElasticIn => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    -(2f32.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * ELASTIC_C4).sin()
+                }
+            }
+            Ease::ElasticOut => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    2f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * ELASTIC_C4).sin() + 1.0
+                }
+            }
+            Ease::ElasticInOut => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else if t < 0.5 {
+                    -(2f32.powf(20.0 * t - 10.0) * ((20.0 * t - 11.125) * ELASTIC_C5).sin()) / 2.0
+                } else {
+                    (2f32.powf(-20.0 * t + 10.0) * ((20.0 * t - 11.125) * ELASTIC_C5).sin()) / 2.0
+                        + 1.0
+                }
+            }
+            Ease::BounceIn => 1.0 - bounce_out(1.0 - t),
+            Ease::BounceOut => bounce_out(t),
+            Ease::BounceInOut => {
+                if t < 0.5 {
+                    (1.0 - bounce_out(1.0 - 2.0 * t)) / 2.0
+                } else {
+                    (1.0 + bounce_out(2.0 * t - 1.0)) / 2.0
+                }
+            }
+            Ease::Steps(steps) => {
+                if steps == 0 || t == 1.0 {
+                    t
+                } else {
+                    (t * steps as f32).floor() / steps as f32
+                }
+            }
+        }
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_ease_endpoints() {
+        use super::Ease;
+
+        let eases = [
+            Ease::Linear,
+            Ease::QuadIn,
+            Ease::QuadOut,
+            Ease::QuadInOut,
+            Ease::CubicIn,
+            Ease::CubicOut,
+            Ease::CubicInOut,
+            Ease::SineIn,
+            Ease::SineOut,
+            Ease::SineInOut,
+            Ease::ExpoIn,
+            Ease::ExpoOut,
+            Ease::ExpoInOut,
+            Ease::BackIn,
+            Ease::BackOut,
+            Ease::BackInOut,
+            Ease::ElasticIn,
+            Ease::ElasticOut,
+            Ease::ElasticInOut,
+            Ease::BounceIn,
+            Ease::BounceOut,
+            Ease::BounceInOut,
+            Ease::Steps(4),
+        ];
+
+        for ease in eases {
+            assert!(ease.sample(0.0).abs() < 1e-4, "{:?}", ease);
+            assert!((ease.sample(1.0) - 1.0).abs() < 1e-4, "{:?}", ease);
+        }
+    }
+
+    #[test]
+    fn test_ease_shape() {
+        use super::Ease;
+
+        assert_eq!(Ease::QuadIn.sample(0.5), 0.25);
+        assert_eq!(Ease::QuadOut.sample(0.5), 0.75);
+        assert_eq!(Ease::QuadInOut.sample(0.5), 0.5);
+        assert!(Ease::BackIn.sample(0.2) < 0.0);
+        assert!(Ease::ElasticOut.sample(0.2) > 1.0);
+
+        assert_eq!(Ease::Steps(4).sample(0.2), 0.0);
+        assert_eq!(Ease::Steps(4).sample(0.3), 0.25);
+        assert_eq!(Ease::Steps(4).sample(0.99), 0.75);
+    }
+}
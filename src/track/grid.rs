@@ -0,0 +1,99 @@
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::value::TrackValue;
+
+use super::{Ease, InterpolationMode, Keyframe, Tangent, TrackDataContainer};
+
+///固定帧网格，用于按帧编辑关键帧，最终转换为按时间排列的关键帧
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameGrid {
+    pub frame_duration: f32,
+    pub frame_count: usize,
+}
+
+impl FrameGrid {
+    pub fn new(frame_duration: f32, frame_count: usize) -> Self {
+        Self {
+            frame_duration,
+            frame_count,
+        }
+    }
+
+    ///网格的总时长
+    pub fn duration(&self) -> f32 {
+        self.frame_duration * self.frame_count as f32
+    }
+
+    ///第 frame 帧对应的时间
+    pub fn time(&self, frame: usize) -> f32 {
+        self.frame_duration * frame as f32
+    }
+
+    ///在第 frame 帧创建关键帧
+    pub fn keyframe(&self, frame: usize, value: TrackValue) -> Keyframe {
+        Keyframe::new(self.time(frame), value)
+    }
+}
+
+///按帧网格保存的关键帧，切线的 x 以帧为单位
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct GridKeyframe {
+    pub id: Uuid,
+    pub location: usize,
+    pub value: TrackValue,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_tangent: Option<Tangent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub out_tangent: Option<Tangent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ease: Option<Ease>,
+}
+
+///按帧网格编辑的轨道数据，加载时转换为 TrackDataContainer
+#[derive(Clone, Deserialize, Serialize)]
+pub struct GridTrackData {
+    //关键帧数据
+    pub keyframes: HashMap<Uuid, GridKeyframe>,
+    //差值模式
+    pub mode: InterpolationMode,
+    //每帧的时间
+    pub frame_duration: f32,
+    //关键帧索引
+    pub frame_indexs: Vec<Option<Uuid>>,
+}
+
+impl GridTrackData {
+    pub fn grid(&self) -> FrameGrid {
+        FrameGrid::new(self.frame_duration, self.frame_indexs.len())
+    }
+}
+
+impl From<GridTrackData> for TrackDataContainer {
+    fn from(data: GridTrackData) -> Self {
+        let grid = data.grid();
+        let scale_tangent =
+            |tangent: Tangent| Tangent::new(tangent.x * grid.frame_duration, tangent.y);
+
+        let mut container = TrackDataContainer::new(grid.duration());
+        container.set_mode(data.mode);
+
+        for (frame, uuid) in data.frame_indexs.iter().enumerate() {
+            let Some(keyframe) = uuid.and_then(|uuid| data.keyframes.get(&uuid)) else {
+                continue;
+            };
+
+            container.add_keyframe(Keyframe {
+                id: keyframe.id,
+                time: grid.time(frame),
+                value: keyframe.value.clone(),
+                in_tangent: keyframe.in_tangent.map(scale_tangent),
+                out_tangent: keyframe.out_tangent.map(scale_tangent),
+                ease: keyframe.ease,
+            });
+        }
+
+        container
+    }
+}
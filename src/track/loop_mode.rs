@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+
+///动画播放到末尾后的处理方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum LoopMode {
+    //播放一次并停在最后一帧
+    Once,
+    //无限循环
+    #[default]
+    Loop,
+    //正向播放后反向播放，无限往返
+    PingPong,
+    //循环 n 次后停在最后一帧
+    Repeat(u32),
+}
+
+impl LoopMode {
+    ///播放是否已经结束，无限循环的模式永远不会结束
+    pub fn is_finished(&self, time: f32, duration: f32) -> bool {
+        match *self {
+            LoopMode::Once => time >= duration,
+            LoopMode::Repeat(count) => time >= duration * count as f32,
+            LoopMode::Loop | LoopMode::PingPong => false,
+        }
+    }
+
+    ///是否会播放结束
+    pub fn is_finite(&self) -> bool {
+        matches!(self, LoopMode::Once | LoopMode::Repeat(_))
+    }
+
+    ///已经完成的循环次数，往返模式完成一次往返计为一次循环
+    pub fn loop_count(&self, time: f32, duration: f32) -> u32 {
+        if duration <= 0.0 {
+            return 0;
+        }
+
+        let cycle = match self {
+            LoopMode::Once => return 0,
+            LoopMode::PingPong => 2.0 * duration,
+            LoopMode::Loop | LoopMode::Repeat(_) => duration,
+        };
+
+        //反向播放越过 0 后循环序号为负数，按经过的循环数计算
+        let count = ((time / cycle).floor() as i64).unsigned_abs() as u32;
+
+        match *self {
+            LoopMode::Repeat(repeat) => count.min(repeat.saturating_sub(1)),
+            _ => count,
+        }
+    }
+
+    ///末尾的关键帧是否与下一次循环开头的关键帧相连
+    pub fn wraps(&self, time: f32, duration: f32) -> bool {
+        match self {
+            LoopMode::Loop => true,
+            LoopMode::Repeat(_) => !self.is_finished(time, duration),
+            LoopMode::Once | LoopMode::PingPong => false,
+        }
+    }
+
+    ///将播放时间映射到 0 到 duration 之间
+    pub fn local_time(&self, time: f32, duration: f32) -> f32 {
+        if duration <= 0.0 {
+            return 0.0;
+        }
+
+        if self.is_finished(time, duration) {
+            return duration;
+        }
+
+        match self {
+            LoopMode::Once => time.clamp(0.0, duration),
+            LoopMode::Loop | LoopMode::Repeat(_) => time.rem_euclid(duration),
+            LoopMode::PingPong => {
+                let time = time.rem_euclid(2.0 * duration);
+
+                if time > duration {
+                    2.0 * duration - time
+                } else {
+                    time
+                }
+            }
+        }
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_local_time() {
+        use super::LoopMode;
+
+        assert_eq!(LoopMode::Loop.local_time(2.5, 2.0), 0.5);
+        assert_eq!(LoopMode::Once.local_time(2.5, 2.0), 2.0);
+        assert_eq!(LoopMode::PingPong.local_time(2.5, 2.0), 1.5);
+        assert_eq!(LoopMode::PingPong.local_time(4.5, 2.0), 0.5);
+        assert_eq!(LoopMode::Repeat(2).local_time(2.5, 2.0), 0.5);
+        assert_eq!(LoopMode::Repeat(2).local_time(4.5, 2.0), 2.0);
+    }
+
+    #[test]
+    fn test_is_finished() {
+        use super::LoopMode;
+
+        assert!(!LoopMode::Once.is_finished(1.5, 2.0));
+        assert!(LoopMode::Once.is_finished(2.0, 2.0));
+        assert!(!LoopMode::Repeat(3).is_finished(5.0, 2.0));
+        assert!(LoopMode::Repeat(3).is_finished(6.0, 2.0));
+        assert!(!LoopMode::Loop.is_finished(100.0, 2.0));
+        assert!(!LoopMode::PingPong.is_finished(100.0, 2.0));
+    }
+
+    #[test]
+    fn test_loop_count() {
+        use super::LoopMode;
+
+        assert_eq!(LoopMode::Once.loop_count(5.0, 2.0), 0);
+        assert_eq!(LoopMode::Loop.loop_count(1.5, 2.0), 0);
+        assert_eq!(LoopMode::Loop.loop_count(5.0, 2.0), 2);
+        assert_eq!(LoopMode::PingPong.loop_count(5.0, 2.0), 1);
+        assert_eq!(LoopMode::Repeat(2).loop_count(3.0, 2.0), 1);
+        assert_eq!(LoopMode::Repeat(2).loop_count(9.0, 2.0), 1);
+
+        //反向播放越过 0
+        assert_eq!(LoopMode::Loop.loop_count(-0.5, 1.0), 1);
+        assert_eq!(LoopMode::Loop.loop_count(-1.5, 1.0), 2);
+        assert_eq!(LoopMode::PingPong.loop_count(-1.5, 1.0), 1);
+        assert_eq!(LoopMode::PingPong.loop_count(-2.5, 1.0), 2);
+    }
+}
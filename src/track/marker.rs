@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+
+use super::LoopMode;
+
+///动画中的命名标记，播放经过时触发事件
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Marker {
+    pub name: String,
+    //标记所在的时间(秒)
+    pub time: f32,
+    //随事件发送的数据
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub payload: serde_json::Value,
+}
+
+impl Marker {
+    pub fn new(name: &str, time: f32) -> Self {
+        Self {
+            name: name.to_string(),
+            time,
+            payload: serde_json::Value::Null,
+        }
+    }
+
+    pub fn with_payload(mut self, payload: serde_json::Value) -> Self {
+        self.payload = payload;
+        self
+    }
+}
+
+///按时间排序的标记轨道
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(from = "Vec<Marker>", into = "Vec<Marker>")]
+pub struct MarkerTrack {
+    markers: Vec<Marker>,
+}
+
+impl From<Vec<Marker>> for MarkerTrack {
+    fn from(mut markers: Vec<Marker>) -> Self {
+        markers.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        Self { markers }
+    }
+}
+
+impl From<MarkerTrack> for Vec<Marker> {
+    fn from(track: MarkerTrack) -> Self {
+        track.markers
+    }
+}
+
+impl MarkerTrack {
+    pub fn add_marker(&mut self, marker: Marker) {
+        let index = self
+            .markers
+            .partition_point(|value| value.time <= marker.time);
+
+        self.markers.insert(index, marker);
+    }
+
+    pub fn markers(&self) -> &[Marker] {
+        &self.markers
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.markers.is_empty()
+    }
+
+    ///播放时间从 from 前进到 to 时经过的标记，按经过的顺序返回
+    ///
+    ///区间为 [from, to)，播放结束的那一帧包含 to，一帧内跨过多次循环时每次循环都会返回
+    pub fn crossed(&self, from: f32, to: f32, loop_mode: LoopMode, duration: f32) -> Vec<&Marker> {
+        let mut crossed = vec![];
+
+        if self.markers.is_empty() || duration <= 0.0 || from >= to {
+            return crossed;
+        }
+
+        let inclusive_end = loop_mode.is_finished(to, duration);
+
+        //反向播放越过 0 时循环序号为负数
+        let mut first_cycle = (from / duration).floor() as i64;
+        let mut last_cycle = (to / duration).floor() as i64;
+
+        match loop_mode {
+            LoopMode::Once => {
+                first_cycle = first_cycle.max(0);
+                last_cycle = 0;
+            }
+            LoopMode::Repeat(count) => last_cycle = last_cycle.min(count.saturating_sub(1) as i64),
+            LoopMode::Loop | LoopMode::PingPong => {}
+        }
+
+        for cycle in first_cycle..=last_cycle {
+            let base = cycle as f32 * duration;
+            let backward = loop_mode == LoopMode::PingPong && cycle.rem_euclid(2) == 1;
+
+            for index in 0..self.markers.len() {
+                let (marker, time) = if backward {
+                    let marker = &self.markers[self.markers.len() - 1 - index];
+
+                    //往返的折返点由正向播放的循环负责，避免重复触发
+                    if marker.time <= 0.0 || marker.time >= duration {
+                        continue;
+                    }
+
+                    (marker, base + duration - marker.time)
+                } else {
+                    let marker = &self.markers[index];
+                    (marker, base + marker.time)
+                };
+
+                if time >= from && (time < to || (inclusive_end && time == to)) {
+                    crossed.push(marker);
+                }
+            }
+        }
+
+        crossed
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_crossed_markers() {
+        use super::{Marker, MarkerTrack};
+        use crate::track::LoopMode;
+
+        let mut track = MarkerTrack::default();
+        track.add_marker(Marker::new("step_r", 0.5));
+        track.add_marker(Marker::new("step_l", 0.0));
+
+        let names = |markers: Vec<&Marker>| -> Vec<String> {
+            markers.iter().map(|marker| marker.name.clone()).collect()
+        };
+
+        assert_eq!(
+            names(track.crossed(0.0, 0.1, LoopMode::Loop, 1.0)),
+            vec!["step_l"]
+        );
+        assert!(track.crossed(0.1, 0.4, LoopMode::Loop, 1.0).is_empty());
+
+        //循环边界
+        assert_eq!(
+            names(track.crossed(0.9, 1.1, LoopMode::Loop, 1.0)),
+            vec!["step_l"]
+        );
+
+        //一帧跨过多次循环
+        assert_eq!(
+            names(track.crossed(0.4, 2.2, LoopMode::Loop, 1.0)),
+            vec!["step_r", "step_l", "step_r", "step_l"]
+        );
+
+        //只播放一次
+        assert_eq!(
+            names(track.crossed(0.4, 2.2, LoopMode::Once, 1.0)),
+            vec!["step_r"]
+        );
+
+        //往返时反向经过标记
+        assert_eq!(
+            names(track.crossed(0.9, 2.1, LoopMode::PingPong, 1.0)),
+            vec!["step_r", "step_l"]
+        );
+        assert!(track.crossed(2.1, 2.4, LoopMode::PingPong, 1.0).is_empty());
+
+        //反向播放越过 0
+        assert_eq!(
+            names(track.crossed(-0.6, -0.4, LoopMode::Loop, 1.0)),
+            vec!["step_r"]
+        );
+        assert_eq!(
+            names(track.crossed(-1.1, 0.1, LoopMode::Loop, 1.0)),
+            vec!["step_l", "step_r", "step_l"]
+        );
+        assert_eq!(
+            names(track.crossed(-0.6, -0.4, LoopMode::PingPong, 1.0)),
+            vec!["step_r"]
+        );
+        assert!(track.crossed(-0.6, -0.4, LoopMode::Once, 1.0).is_empty());
+    }
+
+    #[test]
+    fn test_marker_track_serde() {
+        use super::MarkerTrack;
+
+        let track: MarkerTrack = serde_json::from_str(
+            r#"[
+                { "name": "hit_end", "time": 0.6 },
+                { "name": "hit_start", "time": 0.2, "payload": { "damage": 10 } }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(track.markers()[0].name, "hit_start");
+        assert_eq!(track.markers()[0].payload["damage"], 10);
+        assert!(track.markers()[1].payload.is_null());
+    }
+}
@@ -1,5 +1,19 @@
+mod animate_assets;
 mod animate_components;
+mod animate_resources;
+mod curve;
+mod easing;
+mod grid;
+mod loop_mode;
+mod marker;
+#[allow(clippy::module_inception)]
 pub mod track;
 
+pub use animate_assets::*;
 pub use animate_components::*;
+pub use animate_resources::*;
+pub use easing::*;
+pub use grid::*;
+pub use loop_mode::*;
+pub use marker::*;
 pub use track::*;
@@ -1,46 +1,103 @@
-use bevy::{asset::AssetServer, log::warn, reflect::TypeRegistry, utils::HashMap};
+use std::borrow::Cow;
+
+use bevy::{math::Vec2, utils::HashMap};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::value::{BoundValue, ReflectBoundValue, TrackValue, ValueBinding};
+use crate::{
+    assets::CompiledComponent,
+    value::{BoundValue, TrackValue, ValueBinding},
+};
+
+use super::{
+    curve::{cubic_bezier, hermite},
+    Ease, GridTrackData, LoopMode,
+};
 
 #[derive(Clone)]
 pub struct BoundComponentValue(pub Vec<BoundValue>);
 
 impl BoundComponentValue {
-    pub fn get_component_pose(
-        &self,
-        registry: &TypeRegistry,
-        asset_server: &AssetServer,
-    ) -> Option<ComponentPose> {
-        let mut values = vec![];
-
-        for bound_value in self.0.iter() {
-            match bound_value.get_relect_value(registry, asset_server) {
-                Ok(reflect) => {
-                    values.push(ReflectBoundValue {
-                        value: reflect,
-                        path: bound_value.binding.path.clone(),
-                    });
-                }
+    ///按字段路径与 other 混合，只存在于一边的字段保持原值
+    ///
+    ///类型不同无法混合的字段在 weight 达到 threshold 时切换为 other 的值
+    pub fn blend_with(&mut self, other: &Self, weight: f32, threshold: f32) {
+        self.blend_with_filter(other, weight, threshold, |_| true);
+    }
 
-                Err(e) => {
-                    warn!("get_relect_value error: {}", e);
-                }
+    ///只混合 other 中 filter 返回 true 的字段
+    pub(crate) fn blend_with_filter(
+        &mut self,
+        other: &Self,
+        weight: f32,
+        threshold: f32,
+        filter: impl Fn(&BoundValue) -> bool,
+    ) {
+        for other_value in other.0.iter().filter(|value| filter(value)) {
+            let Some(value) = self
+                .0
+                .iter_mut()
+                .find(|value| value.binding.path == other_value.binding.path)
+            else {
+                self.0.push(other_value.clone());
+                continue;
+            };
+
+            if value
+                .blend_with_threshold(other_value, weight, threshold)
+                .is_err()
+                && weight >= threshold
+            {
+                value.value.clone_from(&other_value.value);
             }
         }
+    }
 
-        if values.is_empty() {
-            return None;
-        } else {
-            Some(ComponentPose { values })
+    ///结果与 from.blend_with(self) 相同，但写入 self，用于复用 self 的缓冲区
+    pub fn blend_from(&mut self, from: &Self, weight: f32, threshold: f32) {
+        for from_value in from.0.iter() {
+            let Some(value) = self
+                .0
+                .iter_mut()
+                .find(|value| value.binding.path == from_value.binding.path)
+            else {
+                self.0.push(from_value.clone());
+                continue;
+            };
+
+            value.blend_from(from_value, weight, threshold);
         }
     }
-}
 
-#[derive(Clone)]
-pub struct ComponentPose {
-    pub values: Vec<ReflectBoundValue>,
+    ///按字段路径叠加 other * weight，self 中没有的字段无法叠加会被忽略
+    ///
+    ///无法叠加的字段在 weight 达到 threshold 时切换为 other 的值
+    pub fn add_with(&mut self, other: &Self, weight: f32, threshold: f32) {
+        self.add_with_filter(other, weight, threshold, |_| true);
+    }
+
+    ///只叠加 other 中 filter 返回 true 的字段
+    pub(crate) fn add_with_filter(
+        &mut self,
+        other: &Self,
+        weight: f32,
+        threshold: f32,
+        filter: impl Fn(&BoundValue) -> bool,
+    ) {
+        for other_value in other.0.iter().filter(|value| filter(value)) {
+            let Some(value) = self
+                .0
+                .iter_mut()
+                .find(|value| value.binding.path == other_value.binding.path)
+            else {
+                continue;
+            };
+
+            if value.add_weighted(other_value, weight).is_err() && weight >= threshold {
+                value.value.clone_from(&other_value.value);
+            }
+        }
+    }
 }
 
 #[derive(Clone, Deserialize, Serialize, Default)]
@@ -53,16 +110,63 @@ impl ComponentTrack {
         self.values.insert(track.binding.path.clone(), track);
     }
 
-    pub(crate) fn fetch(&self, time: f32) -> BoundComponentValue {
-        let mut bound_values = vec![];
+    ///把所有轨道转换为相对 reference_time 时刻的差值
+    pub fn make_additive(&mut self, reference_time: f32, loop_mode: LoopMode) {
+        for track in self.values.values_mut() {
+            track.make_additive(reference_time, loop_mode);
+        }
+    }
+
+    pub(crate) fn fetch(
+        &self,
+        time: f32,
+        loop_mode: LoopMode,
+        compiled: Option<&CompiledComponent>,
+    ) -> BoundComponentValue {
+        let mut bound_values = BoundComponentValue(vec![]);
+        self.fetch_into(time, loop_mode, compiled, &mut bound_values);
+        bound_values
+    }
+
+    ///取值写入 out，复用 out 中已有字段的缓冲区，compiled 提供字段是否无法插值
+    pub(crate) fn fetch_into(
+        &self,
+        time: f32,
+        loop_mode: LoopMode,
+        compiled: Option<&CompiledComponent>,
+        out: &mut BoundComponentValue,
+    ) {
+        out.0
+            .retain(|value| self.values.contains_key(&value.binding.path));
 
         for track in self.values.values() {
-            if let Some(bound_value) = track.fetch(time) {
-                bound_values.push(bound_value);
+            let index = out
+                .0
+                .iter()
+                .position(|value| value.binding.path == track.binding.path);
+
+            let discrete =
+                compiled.is_some_and(|compiled| compiled.is_discrete(&track.binding.path));
+
+            match (track.fetch_value(time, loop_mode), index) {
+                (Some(value), Some(index)) => {
+                    //值没有变化时不复制，资源类型的关键帧大部分时间都不变
+                    if out.0[index].value != *value {
+                        out.0[index].value = value.into_owned();
+                    }
+                    out.0[index].discrete = discrete;
+                }
+                (Some(value), None) => out.0.push(BoundValue {
+                    binding: track.binding.clone(),
+                    value: value.into_owned(),
+                    discrete,
+                }),
+                (None, Some(index)) => {
+                    out.0.swap_remove(index);
+                }
+                (None, None) => {}
             }
         }
-
-        BoundComponentValue(bound_values)
     }
 }
 
@@ -71,129 +175,397 @@ pub struct Track {
     enabled: bool,
     pub frames: TrackDataContainer,
     binding: ValueBinding,
+    //覆盖动画的循环模式
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    loop_mode: Option<LoopMode>,
 }
 
 impl Track {
-    pub fn new(binding: ValueBinding, frame_duration: f32, frame_count: usize) -> Self {
+    ///创建时长为 duration 秒的轨道
+    pub fn new(binding: ValueBinding, duration: f32) -> Self {
         Self {
             enabled: true,
-            frames: TrackDataContainer::new(frame_duration, frame_count),
+            frames: TrackDataContainer::new(duration),
             binding,
+            loop_mode: None,
         }
     }
     pub fn add_keyframe(&mut self, key_frame: Keyframe) {
         self.frames.add_keyframe(key_frame);
     }
 
+    pub fn remove_keyframe(&mut self, id: &Uuid) -> Option<Keyframe> {
+        self.frames.remove_keyframe(id)
+    }
+
+    pub fn set_mode(&mut self, mode: InterpolationMode) {
+        self.frames.set_mode(mode);
+    }
+
+    pub fn binding(&self) -> &ValueBinding {
+        &self.binding
+    }
+
+    ///设置轨道自己的循环模式，为 None 时使用动画的循环模式
+    pub fn set_loop_mode(&mut self, loop_mode: Option<LoopMode>) {
+        self.loop_mode = loop_mode;
+    }
+
     pub fn fetch(&self, time: f32) -> Option<BoundValue> {
-        if !self.enabled {
-            return None;
+        self.fetch_with_loop_mode(time, LoopMode::default())
+    }
+
+    ///把关键帧转换为相对 reference_time 时刻的差值，无法相减的值(例如资源)保持不变
+    pub fn make_additive(&mut self, reference_time: f32, loop_mode: LoopMode) {
+        let loop_mode = self.loop_mode.unwrap_or(loop_mode);
+
+        if let Some(reference) = self
+            .frames
+            .fetch(reference_time, loop_mode)
+            .map(Cow::into_owned)
+        {
+            self.frames.make_additive(&reference);
         }
+    }
 
-        self.frames.fetch(time).map(|value| BoundValue {
+    ///按动画的循环模式取值，轨道设置了循环模式时优先使用轨道的
+    pub fn fetch_with_loop_mode(&self, time: f32, loop_mode: LoopMode) -> Option<BoundValue> {
+        self.fetch_value(time, loop_mode).map(|value| BoundValue {
             binding: self.binding.clone(),
-            value,
+            value: value.into_owned(),
+            discrete: false,
         })
     }
+
+    fn fetch_value(&self, time: f32, loop_mode: LoopMode) -> Option<Cow<'_, TrackValue>> {
+        if !self.enabled {
+            return None;
+        }
+
+        self.frames.fetch(time, self.loop_mode.unwrap_or(loop_mode))
+    }
 }
 
+///按时间排列的关键帧数据，也可以从按帧网格编辑的数据反序列化
 #[derive(Clone, Deserialize, Serialize)]
+#[serde(from = "TrackDataContainerData")]
 pub struct TrackDataContainer {
-    //关键帧数据
-    pub keyframes: HashMap<Uuid, Keyframe>,
+    //按时间排序的关键帧数据
+    keyframes: Vec<Keyframe>,
 
     //差值模式
     mode: InterpolationMode,
 
-    //动画时间
-    frame_duration: f32,
-    //关键帧索引
-    frame_indexs: Vec<Option<Uuid>>,
+    //轨道时长
+    duration: f32,
 }
 
-impl TrackDataContainer {
-    fn add_keyframe(&mut self, key_frame: Keyframe) {
-        if let Some(value) = self.frame_indexs.get_mut(key_frame.location) {
-            *value = Some(key_frame.id.to_owned());
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TrackDataContainerData {
+    Time {
+        keyframes: Vec<Keyframe>,
+        mode: InterpolationMode,
+        duration: f32,
+    },
+    Grid(GridTrackData),
+}
 
-            self.keyframes.insert(key_frame.id.to_owned(), key_frame);
+impl From<TrackDataContainerData> for TrackDataContainer {
+    fn from(data: TrackDataContainerData) -> Self {
+        match data {
+            TrackDataContainerData::Time {
+                mut keyframes,
+                mode,
+                duration,
+            } => {
+                keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+                TrackDataContainer {
+                    keyframes,
+                    mode,
+                    duration,
+                }
+            }
+            TrackDataContainerData::Grid(grid) => grid.into(),
         }
     }
+}
 
-    fn new(frame_duration: f32, frame_count: usize) -> Self {
+//关键帧和展开后的时间
+type SegmentKey<'a> = (&'a Keyframe, f32);
+
+impl TrackDataContainer {
+    ///插入关键帧并保持有序，同一时间已有关键帧时替换
+    pub(crate) fn add_keyframe(&mut self, key_frame: Keyframe) {
+        match self
+            .keyframes
+            .binary_search_by(|keyframe| keyframe.time.total_cmp(&key_frame.time))
+        {
+            Ok(index) => self.keyframes[index] = key_frame,
+            Err(index) => self.keyframes.insert(index, key_frame),
+        }
+    }
+
+    pub(crate) fn remove_keyframe(&mut self, id: &Uuid) -> Option<Keyframe> {
+        let index = self
+            .keyframes
+            .iter()
+            .position(|keyframe| keyframe.id == *id)?;
+
+        Some(self.keyframes.remove(index))
+    }
+
+    pub(crate) fn new(duration: f32) -> Self {
         TrackDataContainer {
-            keyframes: Default::default(),
+            keyframes: vec![],
             mode: InterpolationMode::Constant,
-            frame_duration,
-            frame_indexs: vec![None; frame_count],
+            duration,
         }
     }
 
-    fn fetch(&self, time: f32) -> Option<TrackValue> {
-        let real_time = time % (self.frame_duration * self.frame_indexs.len() as f32);
+    pub(crate) fn set_mode(&mut self, mode: InterpolationMode) {
+        self.mode = mode;
+    }
 
-        let index = real_time / self.frame_duration;
+    pub(crate) fn make_additive(&mut self, reference: &TrackValue) {
+        for key_frame in self.keyframes.iter_mut() {
+            if let Ok(delta) = key_frame.value.difference(reference) {
+                key_frame.value = delta;
+            }
+        }
+    }
+
+    ///修改关键帧的值，不能修改关键帧的时间
+    pub(crate) fn keyframes_mut(&mut self) -> impl Iterator<Item = &mut Keyframe> {
+        self.keyframes.iter_mut()
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    ///二分查找 time 所在区间前后的关键帧，返回关键帧和展开后的时间(可能跨越循环边界)
+    fn find_segment(&self, time: f32) -> Option<(SegmentKey<'_>, SegmentKey<'_>)> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+
+        let start = match next {
+            0 => (last, last.time - self.duration),
+            _ => (&self.keyframes[next - 1], self.keyframes[next - 1].time),
+        };
+
+        let end = match self.keyframes.get(next) {
+            Some(keyframe) => (keyframe, keyframe.time),
+            None => (first, first.time + self.duration),
+        };
+
+        Some((start, end))
+    }
+
+    ///关键帧上的值直接借用，只有插值的结果需要分配
+    fn fetch(&self, time: f32, loop_mode: LoopMode) -> Option<Cow<'_, TrackValue>> {
+        if self.duration <= 0.0 {
+            return None;
+        }
 
-        let index_min = index.floor() as usize;
-        // let index_max = index.ceil() as usize;
+        let real_time = loop_mode.local_time(time, self.duration);
+
+        //不循环时首尾关键帧之外保持首尾的值
+        if !loop_mode.wraps(time, self.duration) {
+            let first = self.keyframes.first()?;
+            let last = self.keyframes.last()?;
+
+            if real_time <= first.time {
+                return Some(Cow::Borrowed(&first.value));
+            }
+
+            if real_time >= last.time {
+                return Some(Cow::Borrowed(&last.value));
+            }
+        }
+
+        let ((start, start_time), (end, end_time)) = self.find_segment(real_time)?;
+
+        match (&self.mode, &start.value) {
+            (InterpolationMode::Constant, _)
+            | (_, TrackValue::Asset(_))
+            | (_, TrackValue::Reflect(_)) => Some(Cow::Borrowed(&start.value)),
+            _ => Some(Cow::Owned(
+                self.sample_segment(start, start_time, end, end_time, real_time),
+            )),
+        }
+    }
+
+    ///根据插值模式计算 start 和 end 两个关键帧之间 time 处的值
+    fn sample_segment(
+        &self,
+        start: &Keyframe,
+        start_time: f32,
+        end: &Keyframe,
+        end_time: f32,
+        time: f32,
+    ) -> TrackValue {
+        let duration = end_time - start_time;
+        let mut t = (time - start_time) / duration;
+
+        if let Some(ease) = start.ease {
+            t = ease.sample(t);
+        }
+
+        let (TrackValue::Number(p0), TrackValue::Number(p1)) = (&start.value, &end.value) else {
+            return start.value.interpolate(&end.value, t);
+        };
 
         match self.mode {
-            InterpolationMode::Constant => {
-                if let Some(uuid) = self.frame_indexs[index_min] {
-                    Some(self.keyframes.get(&uuid).unwrap().value.clone())
-                } else {
-                    None
-                }
+            InterpolationMode::Hermite => {
+                let m0 = start
+                    .out_tangent
+                    .map(|tangent| tangent.slope())
+                    .unwrap_or(0.0);
+                let m1 = end.in_tangent.map(|tangent| tangent.slope()).unwrap_or(0.0);
+
+                TrackValue::Number(hermite(*p0, m0 * duration, *p1, m1 * duration, t))
             }
+            InterpolationMode::CubicBezier => {
+                let out_tangent = start
+                    .out_tangent
+                    .unwrap_or(Tangent::new(duration / 3.0, 0.0));
+                let in_tangent = end.in_tangent.unwrap_or(Tangent::new(-duration / 3.0, 0.0));
+
+                let x = start_time + t * duration;
+
+                let control_start = Vec2::new(
+                    start_time + out_tangent.x.clamp(0.0, duration),
+                    p0 + out_tangent.y,
+                );
+                let control_end = Vec2::new(
+                    end_time + in_tangent.x.clamp(-duration, 0.0),
+                    p1 + in_tangent.y,
+                );
+
+                TrackValue::Number(cubic_bezier(
+                    Vec2::new(start_time, *p0),
+                    control_start,
+                    control_end,
+                    Vec2::new(end_time, *p1),
+                    x,
+                ))
+            }
+            _ => start.value.interpolate(&end.value, t),
         }
     }
 }
 
+///关键帧之间的插值模式
 #[derive(Clone, Deserialize, Serialize)]
 pub enum InterpolationMode {
+    //保持上一个关键帧的值
     Constant,
+    //在前后两个关键帧之间线性插值
+    Linear,
+    //使用关键帧切线作为贝塞尔控制柄，缺省时为平滑的缓入缓出
+    CubicBezier,
+    //使用关键帧切线的斜率做 Hermite 插值，缺省斜率为 0
+    Hermite,
+}
+
+///关键帧切线，x 为相对关键帧的时间偏移(秒)，y 为数值偏移
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy, Deserialize, Serialize)]
+pub struct Tangent {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Tangent {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    ///每秒的数值变化量
+    pub fn slope(&self) -> f32 {
+        if self.x == 0.0 {
+            0.0
+        } else {
+            self.y / self.x
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, PartialOrd, Clone, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 pub struct Keyframe {
     pub id: Uuid,
-    pub location: usize,
+    //关键帧所在的时间(秒)
+    pub time: f32,
     pub value: TrackValue,
+    //进入该关键帧的切线，x 通常为负数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_tangent: Option<Tangent>,
+    //离开该关键帧的切线，x 通常为正数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub out_tangent: Option<Tangent>,
+    //到下一个关键帧之间的缓动函数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ease: Option<Ease>,
 }
 
 impl Keyframe {
-    pub fn new(location: usize, value: TrackValue) -> Self {
+    pub fn new(time: f32, value: TrackValue) -> Self {
         Self {
-            location,
+            time,
             value,
             id: Uuid::new_v4(),
+            in_tangent: None,
+            out_tangent: None,
+            ease: None,
         }
     }
+
+    pub fn with_ease(mut self, ease: Ease) -> Self {
+        self.ease = Some(ease);
+        self
+    }
+
+    pub fn with_tangents(mut self, in_tangent: Tangent, out_tangent: Tangent) -> Self {
+        self.in_tangent = Some(in_tangent);
+        self.out_tangent = Some(out_tangent);
+        self
+    }
 }
 
 mod test {
 
     #[test]
     fn test_track() {
-        use super::{Keyframe, Track, ValueBinding};
+        use super::{Track, ValueBinding};
+        use crate::prelude::FrameGrid;
         use crate::prelude::ShortTypePath;
         use crate::prelude::TrackValue;
 
+        let grid = FrameGrid::new(0.5, 2);
+
         let mut track = Track::new(
             ValueBinding {
                 path: ".a".to_owned(),
                 value_type: ShortTypePath::from_type_path::<bool>(),
             },
-            0.5,
-            2,
+            grid.duration(),
         );
 
-        track.add_keyframe(Keyframe::new(1, TrackValue::Number(0.0)));
-        track.add_keyframe(Keyframe::new(0, TrackValue::Number(1.0)));
+        track.add_keyframe(grid.keyframe(1, TrackValue::Number(0.0)));
+        track.add_keyframe(grid.keyframe(0, TrackValue::Number(1.0)));
 
         let bound_value = track.fetch(0.0);
 
-        assert_eq!(true, bound_value.is_some());
+        assert!(bound_value.is_some());
 
         let bound_value = bound_value.unwrap();
 
@@ -211,4 +583,415 @@ mod test {
 
         assert_eq!(bound_value.value, TrackValue::Number(1.0));
     }
+
+    #[test]
+    fn test_linear_track() {
+        use super::{InterpolationMode, Keyframe, Track, ValueBinding};
+        use crate::prelude::ShortTypePath;
+        use crate::prelude::TrackValue;
+
+        let mut track = Track::new(
+            ValueBinding {
+                path: ".a".to_owned(),
+                value_type: ShortTypePath::from_type_path::<f32>(),
+            },
+            4.0,
+        );
+        track.set_mode(InterpolationMode::Linear);
+
+        track.add_keyframe(Keyframe::new(0.0, TrackValue::Number(0.0)));
+        track.add_keyframe(Keyframe::new(2.0, TrackValue::Number(4.0)));
+
+        assert_eq!(track.fetch(0.0).unwrap().value, TrackValue::Number(0.0));
+        assert_eq!(track.fetch(1.0).unwrap().value, TrackValue::Number(2.0));
+        assert_eq!(track.fetch(2.0).unwrap().value, TrackValue::Number(4.0));
+        assert_eq!(track.fetch(0.5).unwrap().value, TrackValue::Number(1.0));
+    }
+
+    #[test]
+    fn test_linear_track_wrap() {
+        use super::{InterpolationMode, Keyframe, Track, ValueBinding};
+        use crate::prelude::ShortTypePath;
+        use crate::prelude::TrackValue;
+
+        let mut track = Track::new(
+            ValueBinding {
+                path: ".a".to_owned(),
+                value_type: ShortTypePath::from_type_path::<f32>(),
+            },
+            4.0,
+        );
+        track.set_mode(InterpolationMode::Linear);
+
+        track.add_keyframe(Keyframe::new(1.0, TrackValue::Number(0.0)));
+        track.add_keyframe(Keyframe::new(3.0, TrackValue::Number(4.0)));
+
+        //跨越循环边界: 3 -> 1(下一个循环)
+        assert_eq!(track.fetch(3.0).unwrap().value, TrackValue::Number(4.0));
+        assert_eq!(track.fetch(4.0).unwrap().value, TrackValue::Number(2.0));
+        assert_eq!(track.fetch(0.0).unwrap().value, TrackValue::Number(2.0));
+        assert_eq!(track.fetch(0.5).unwrap().value, TrackValue::Number(1.0));
+        assert_eq!(track.fetch(5.0).unwrap().value, TrackValue::Number(0.0));
+    }
+
+    #[test]
+    fn test_linear_track_single_keyframe() {
+        use super::{InterpolationMode, Keyframe, Track, ValueBinding};
+        use crate::prelude::ShortTypePath;
+        use crate::prelude::TrackValue;
+
+        let mut track = Track::new(
+            ValueBinding {
+                path: ".a".to_owned(),
+                value_type: ShortTypePath::from_type_path::<f32>(),
+            },
+            4.0,
+        );
+        track.set_mode(InterpolationMode::Linear);
+
+        track.add_keyframe(Keyframe::new(2.0, TrackValue::Number(3.0)));
+
+        assert_eq!(track.fetch(0.0).unwrap().value, TrackValue::Number(3.0));
+        assert_eq!(track.fetch(3.5).unwrap().value, TrackValue::Number(3.0));
+    }
+
+    #[test]
+    fn test_hermite_track() {
+        use super::{InterpolationMode, Keyframe, Tangent, Track, ValueBinding};
+        use crate::prelude::ShortTypePath;
+        use crate::prelude::TrackValue;
+
+        let mut track = Track::new(
+            ValueBinding {
+                path: ".a".to_owned(),
+                value_type: ShortTypePath::from_type_path::<f32>(),
+            },
+            4.0,
+        );
+        track.set_mode(InterpolationMode::Hermite);
+
+        track.add_keyframe(Keyframe::new(0.0, TrackValue::Number(0.0)));
+        track.add_keyframe(Keyframe::new(2.0, TrackValue::Number(2.0)));
+
+        //缺省切线斜率为 0，中点为两端的平均值
+        assert_eq!(track.fetch(1.0).unwrap().value, TrackValue::Number(1.0));
+
+        //斜率为 1 时与线性插值一致
+        let mut track = Track::new(
+            ValueBinding {
+                path: ".a".to_owned(),
+                value_type: ShortTypePath::from_type_path::<f32>(),
+            },
+            8.0,
+        );
+        track.set_mode(InterpolationMode::Hermite);
+
+        track.add_keyframe(
+            Keyframe::new(0.0, TrackValue::Number(0.0))
+                .with_tangents(Tangent::new(-1.0, -1.0), Tangent::new(1.0, 1.0)),
+        );
+        track.add_keyframe(
+            Keyframe::new(4.0, TrackValue::Number(4.0))
+                .with_tangents(Tangent::new(-1.0, -1.0), Tangent::new(1.0, 1.0)),
+        );
+
+        assert_eq!(track.fetch(1.0).unwrap().value, TrackValue::Number(1.0));
+        assert_eq!(track.fetch(3.0).unwrap().value, TrackValue::Number(3.0));
+    }
+
+    #[test]
+    fn test_cubic_bezier_track() {
+        use super::{InterpolationMode, Keyframe, Tangent, Track, ValueBinding};
+        use crate::prelude::ShortTypePath;
+        use crate::prelude::TrackValue;
+
+        let mut track = Track::new(
+            ValueBinding {
+                path: ".a".to_owned(),
+                value_type: ShortTypePath::from_type_path::<f32>(),
+            },
+            8.0,
+        );
+        track.set_mode(InterpolationMode::CubicBezier);
+
+        track.add_keyframe(Keyframe::new(0.0, TrackValue::Number(0.0)));
+        track.add_keyframe(Keyframe::new(4.0, TrackValue::Number(4.0)));
+
+        let TrackValue::Number(value) = track.fetch(2.0).unwrap().value else {
+            panic!("value is not number");
+        };
+        assert!((value - 2.0).abs() < 1e-4);
+
+        //缓入缓出，起点附近比线性插值慢
+        let TrackValue::Number(value) = track.fetch(1.0).unwrap().value else {
+            panic!("value is not number");
+        };
+        assert!(value < 1.0);
+
+        //控制柄在直线上时与线性插值一致
+        let mut track = Track::new(
+            ValueBinding {
+                path: ".a".to_owned(),
+                value_type: ShortTypePath::from_type_path::<f32>(),
+            },
+            8.0,
+        );
+        track.set_mode(InterpolationMode::CubicBezier);
+
+        track.add_keyframe(
+            Keyframe::new(0.0, TrackValue::Number(0.0))
+                .with_tangents(Tangent::new(-1.0, -1.0), Tangent::new(1.0, 1.0)),
+        );
+        track.add_keyframe(
+            Keyframe::new(3.0, TrackValue::Number(3.0))
+                .with_tangents(Tangent::new(-1.0, -1.0), Tangent::new(1.0, 1.0)),
+        );
+
+        let TrackValue::Number(value) = track.fetch(1.5).unwrap().value else {
+            panic!("value is not number");
+        };
+        assert!((value - 1.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_keyframe_without_tangents() {
+        use super::Keyframe;
+        use crate::prelude::TrackValue;
+
+        let keyframe: Keyframe = serde_json::from_str(
+            r#"{
+                "id": "f327472e-96e5-4118-bf6a-d0104b4f3a9b",
+                "time": 1.0,
+                "value": { "Number": 1.0 }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(keyframe.value, TrackValue::Number(1.0));
+        assert_eq!(keyframe.in_tangent, None);
+        assert_eq!(keyframe.out_tangent, None);
+
+        let json = serde_json::to_string(&keyframe).unwrap();
+        assert!(!json.contains("tangent"));
+    }
+
+    #[test]
+    fn test_ease_track() {
+        use super::{Ease, InterpolationMode, Keyframe, Track, ValueBinding};
+        use crate::prelude::ShortTypePath;
+        use crate::prelude::TrackValue;
+
+        let mut track = Track::new(
+            ValueBinding {
+                path: ".a".to_owned(),
+                value_type: ShortTypePath::from_type_path::<f32>(),
+            },
+            4.0,
+        );
+        track.set_mode(InterpolationMode::Linear);
+
+        track.add_keyframe(Keyframe::new(0.0, TrackValue::Number(0.0)).with_ease(Ease::QuadIn));
+        track.add_keyframe(Keyframe::new(2.0, TrackValue::Number(4.0)));
+
+        assert_eq!(track.fetch(1.0).unwrap().value, TrackValue::Number(1.0));
+        //没有缓动的区间保持线性
+        assert_eq!(track.fetch(3.0).unwrap().value, TrackValue::Number(2.0));
+
+        let keyframe: Keyframe = serde_json::from_str(
+            r#"{
+                "id": "f327472e-96e5-4118-bf6a-d0104b4f3a9b",
+                "time": 1.0,
+                "value": { "Number": 1.0 },
+                "ease": "ElasticOut"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(keyframe.ease, Some(Ease::ElasticOut));
+
+        let keyframe: Keyframe = serde_json::from_str(
+            r#"{
+                "id": "f327472e-96e5-4118-bf6a-d0104b4f3a9b",
+                "time": 1.0,
+                "value": { "Number": 1.0 },
+                "ease": { "Steps": 3 }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(keyframe.ease, Some(Ease::Steps(3)));
+    }
+
+    #[test]
+    fn test_time_track() {
+        use super::{InterpolationMode, Keyframe, Track, ValueBinding};
+        use crate::prelude::ShortTypePath;
+        use crate::prelude::TrackValue;
+
+        let mut track = Track::new(
+            ValueBinding {
+                path: ".a".to_owned(),
+                value_type: ShortTypePath::from_type_path::<f32>(),
+            },
+            10.0,
+        );
+        track.set_mode(InterpolationMode::Linear);
+
+        //乱序插入，保持按时间排序
+        track.add_keyframe(Keyframe::new(7.25, TrackValue::Number(2.0)));
+        track.add_keyframe(Keyframe::new(0.125, TrackValue::Number(0.0)));
+        track.add_keyframe(Keyframe::new(3.0, TrackValue::Number(1.0)));
+
+        let times: Vec<f32> = track
+            .frames
+            .keyframes()
+            .iter()
+            .map(|keyframe| keyframe.time)
+            .collect();
+        assert_eq!(times, vec![0.125, 3.0, 7.25]);
+
+        assert_eq!(track.fetch(0.125).unwrap().value, TrackValue::Number(0.0));
+        assert_eq!(track.fetch(5.125).unwrap().value, TrackValue::Number(1.5));
+
+        //同一时间的关键帧被替换
+        let keyframe = Keyframe::new(3.0, TrackValue::Number(5.0));
+        let id = keyframe.id;
+        track.add_keyframe(keyframe);
+        assert_eq!(track.frames.keyframes().len(), 3);
+        assert_eq!(track.fetch(3.0).unwrap().value, TrackValue::Number(5.0));
+
+        assert!(track.remove_keyframe(&id).is_some());
+        assert_eq!(track.frames.keyframes().len(), 2);
+    }
+
+    #[test]
+    fn test_grid_track_data() {
+        use super::{Tangent, TrackDataContainer};
+        use crate::prelude::TrackValue;
+
+        let container: TrackDataContainer = serde_json::from_str(
+            r#"{
+                "keyframes": {
+                    "f327472e-96e5-4118-bf6a-d0104b4f3a9b": {
+                        "id": "f327472e-96e5-4118-bf6a-d0104b4f3a9b",
+                        "location": 3,
+                        "value": { "Number": 1.0 },
+                        "out_tangent": { "x": 2.0, "y": 1.0 }
+                    },
+                    "3c47f33b-d075-4d75-97f3-01c434aa3010": {
+                        "id": "3c47f33b-d075-4d75-97f3-01c434aa3010",
+                        "location": 0,
+                        "value": { "Number": 0.0 }
+                    }
+                },
+                "mode": "Constant",
+                "frame_duration": 0.1,
+                "frame_indexs": [
+                    "3c47f33b-d075-4d75-97f3-01c434aa3010",
+                    null,
+                    null,
+                    "f327472e-96e5-4118-bf6a-d0104b4f3a9b"
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert!((container.duration() - 0.4).abs() < 1e-6);
+
+        let keyframes = container.keyframes();
+        assert_eq!(keyframes.len(), 2);
+        assert_eq!(keyframes[0].time, 0.0);
+        assert!((keyframes[1].time - 0.3).abs() < 1e-6);
+        assert_eq!(keyframes[1].value, TrackValue::Number(1.0));
+        assert_eq!(keyframes[1].out_tangent, Some(Tangent::new(2.0 * 0.1, 1.0)));
+
+        //序列化为按时间排列的格式
+        let json = serde_json::to_string(&container).unwrap();
+        assert!(!json.contains("frame_indexs"));
+
+        let container: TrackDataContainer = serde_json::from_str(&json).unwrap();
+        assert_eq!(container.keyframes().len(), 2);
+    }
+
+    #[test]
+    fn test_track_loop_mode() {
+        use super::{InterpolationMode, Keyframe, LoopMode, Track, ValueBinding};
+        use crate::prelude::ShortTypePath;
+        use crate::prelude::TrackValue;
+
+        let mut track = Track::new(
+            ValueBinding {
+                path: ".a".to_owned(),
+                value_type: ShortTypePath::from_type_path::<f32>(),
+            },
+            4.0,
+        );
+        track.set_mode(InterpolationMode::Linear);
+
+        track.add_keyframe(Keyframe::new(0.0, TrackValue::Number(0.0)));
+        track.add_keyframe(Keyframe::new(2.0, TrackValue::Number(4.0)));
+
+        //循环时最后一个关键帧之后向第一个关键帧插值
+        assert_eq!(
+            track
+                .fetch_with_loop_mode(3.0, LoopMode::Loop)
+                .unwrap()
+                .value,
+            TrackValue::Number(2.0)
+        );
+
+        //播放一次时保持最后一个关键帧的值
+        assert_eq!(
+            track
+                .fetch_with_loop_mode(3.0, LoopMode::Once)
+                .unwrap()
+                .value,
+            TrackValue::Number(4.0)
+        );
+        assert_eq!(
+            track
+                .fetch_with_loop_mode(10.0, LoopMode::Once)
+                .unwrap()
+                .value,
+            TrackValue::Number(4.0)
+        );
+
+        assert_eq!(
+            track
+                .fetch_with_loop_mode(5.0, LoopMode::PingPong)
+                .unwrap()
+                .value,
+            TrackValue::Number(4.0)
+        );
+        assert_eq!(
+            track
+                .fetch_with_loop_mode(7.0, LoopMode::PingPong)
+                .unwrap()
+                .value,
+            TrackValue::Number(2.0)
+        );
+
+        assert_eq!(
+            track
+                .fetch_with_loop_mode(5.0, LoopMode::Repeat(2))
+                .unwrap()
+                .value,
+            TrackValue::Number(2.0)
+        );
+        assert_eq!(
+            track
+                .fetch_with_loop_mode(8.5, LoopMode::Repeat(2))
+                .unwrap()
+                .value,
+            TrackValue::Number(4.0)
+        );
+
+        //轨道的循环模式覆盖动画的循环模式
+        track.set_loop_mode(Some(LoopMode::Once));
+        assert_eq!(
+            track
+                .fetch_with_loop_mode(3.0, LoopMode::Loop)
+                .unwrap()
+                .value,
+            TrackValue::Number(4.0)
+        );
+    }
 }
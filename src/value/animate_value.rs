@@ -1,94 +1,494 @@
-use crate::prelude::ShortTypePath;
-
-use super::{ReflectError, TrackValue};
-use bevy::{
-    asset::{Asset, AssetServer, Handle},
-    prelude::Reflect,
-    reflect::{FromType, TypePath},
-};
-use serde::{Deserialize, Serialize};
-
-impl<A: AnimateValue> FromType<A> for AnimateValueFns {
-    fn from_type() -> Self {
-        AnimateValueFns {
-            reflect: A::get_reflect_value,
-        }
-    }
-}
-
-#[derive(Clone, Debug, PartialEq, Reflect, Deserialize, Serialize, PartialOrd, Hash, Eq)]
-pub struct AnimationValueAssetPath {
-    pub path: String,
-    pub type_path: ShortTypePath,
-}
-
-#[derive(Clone)]
-pub struct AnimateValueFns {
-    pub reflect:
-        fn(&TrackValue, asset_server: &AssetServer) -> Result<Box<dyn Reflect>, ReflectError>,
-}
-
-impl AnimateValueFns {
-    pub fn new<A: AnimateValue>() -> Self {
-        AnimateValueFns {
-            reflect: A::get_reflect_value,
-        }
-    }
-}
-
-pub trait AnimateValue: Reflect + TypePath {
-    fn get_reflect_value(
-        value: &TrackValue,
-        asset_server: &AssetServer,
-    ) -> Result<Box<dyn Reflect>, ReflectError>;
-}
-
-impl AnimateValue for bool {
-    fn get_reflect_value(
-        value: &TrackValue,
-        _asset_server: &AssetServer,
-    ) -> Result<Box<dyn Reflect>, ReflectError> {
-        match value {
-            TrackValue::Number(number) => return Ok(Box::new(number.ne(&0.0))),
-            _ => {
-                return Err(ReflectError::Kind(format!("TrackValue is not valid.")));
-            }
-        }
-    }
-}
-
-impl AnimateValue for usize {
-    fn get_reflect_value(
-        value: &TrackValue,
-        _asset_server: &AssetServer,
-    ) -> Result<Box<dyn Reflect>, ReflectError> {
-        match value {
-            TrackValue::Number(number) => return Ok(Box::new(*number as usize)),
-            _ => {
-                return Err(ReflectError::Kind(format!("TrackValue is not valid.")));
-            }
-        }
-    }
-}
-
-impl<A: Asset> AnimateValue for Handle<A> {
-    fn get_reflect_value(
-        value: &TrackValue,
-        asset_server: &AssetServer,
-    ) -> Result<Box<dyn Reflect>, ReflectError> {
-        match value {
-            TrackValue::Asset(asset) => {
-                if asset.type_path != ShortTypePath::from_type_path::<Self>() {
-                    return Err(ReflectError::Kind(format!("asset type mismatch.")));
-                } else {
-                    let handle: Self = asset_server.load(asset.path.clone());
-
-                    return Ok(Box::new(handle));
-                }
-            }
-            _ => {
-                return Err(ReflectError::Kind(format!("TrackValue is not valid.")));
-            }
-        }
-    }
-}
+use crate::prelude::ShortTypePath;
+
+use super::{AssetPath, ReflectError, TrackValue};
+use bevy::{
+    asset::{Asset, AssetServer, Handle, LoadContext, UntypedHandle},
+    color::Color,
+    math::{Quat, Vec2, Vec3, Vec4},
+    prelude::{Reflect, Visibility},
+    reflect::{FromType, TypePath},
+    ui::Val,
+};
+use serde::{Deserialize, Serialize};
+
+impl<A: AnimateValue> FromType<A> for AnimateValueFns {
+    fn from_type() -> Self {
+        AnimateValueFns::new::<A>()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Reflect, Deserialize, Serialize, PartialOrd, Hash, Eq)]
+pub struct AnimationValueAssetPath {
+    pub path: String,
+    pub type_path: ShortTypePath,
+}
+
+pub type ApplyValueFn = fn(
+    &TrackValue,
+    field: &mut dyn Reflect,
+    asset_server: &AssetServer,
+) -> Result<(), ReflectError>;
+
+pub type LoadValueFn = for<'a> fn(&AssetPath, &mut LoadContext<'a>) -> Option<UntypedHandle>;
+
+#[derive(Clone)]
+pub struct AnimateValueFns {
+    pub apply: ApplyValueFn,
+    pub load: LoadValueFn,
+    pub discrete: bool,
+}
+
+impl AnimateValueFns {
+    pub fn new<A: AnimateValue>() -> Self {
+        AnimateValueFns {
+            apply: A::apply_value,
+            load: A::load_dependency,
+            discrete: A::DISCRETE,
+        }
+    }
+
+    ///没有实现 AnimateValue 的类型使用的函数，只支持 TrackValue::Reflect 的关键帧
+    pub fn reflect_only() -> Self {
+        AnimateValueFns {
+            apply: |value, field, _asset_server| apply_track_reflect_value(value, field),
+            load: |_asset, _load_context| None,
+            discrete: false,
+        }
+    }
+}
+
+fn get_track_reflect_value(value: &TrackValue) -> Result<&dyn Reflect, ReflectError> {
+    match value {
+        TrackValue::Reflect(value) => value
+            .value()
+            .ok_or_else(|| ReflectError::Kind("reflect value is not resolved.".to_string())),
+        _ => Err(ReflectError::Kind("TrackValue is not valid.".to_string())),
+    }
+}
+
+fn apply_track_reflect_value(
+    value: &TrackValue,
+    field: &mut dyn Reflect,
+) -> Result<(), ReflectError> {
+    field
+        .try_apply(get_track_reflect_value(value)?)
+        .map_err(|e| ReflectError::Kind(e.to_string()))
+}
+
+pub trait AnimateValue: Reflect + TypePath + Sized {
+    ///无法插值的类型，混合时在权重达到阈值时切换
+    const DISCRETE: bool = false;
+
+    fn from_track_value(
+        value: &TrackValue,
+        asset_server: &AssetServer,
+    ) -> Result<Self, ReflectError>;
+
+    ///加载 EntityAnimations 时把资源作为依赖加载，返回的强引用保存在关键帧中
+    fn load_dependency(
+        _asset: &AssetPath,
+        _load_context: &mut LoadContext,
+    ) -> Option<UntypedHandle> {
+        None
+    }
+
+    ///直接写入组件的字段，字段类型相同时不需要分配
+    fn apply_value(
+        value: &TrackValue,
+        field: &mut dyn Reflect,
+        asset_server: &AssetServer,
+    ) -> Result<(), ReflectError> {
+        if let TrackValue::Reflect(_) = value {
+            return apply_track_reflect_value(value, field);
+        }
+
+        let value = Self::from_track_value(value, asset_server)?;
+
+        match field.downcast_mut::<Self>() {
+            Some(field) => {
+                *field = value;
+                Ok(())
+            }
+            None => field
+                .try_apply(&value)
+                .map_err(|e| ReflectError::Kind(e.to_string())),
+        }
+    }
+}
+
+impl AnimateValue for bool {
+    const DISCRETE: bool = true;
+
+    fn from_track_value(
+        value: &TrackValue,
+        _asset_server: &AssetServer,
+    ) -> Result<Self, ReflectError> {
+        match value {
+            TrackValue::Number(number) => Ok(number.ne(&0.0)),
+            _ => Err(ReflectError::Kind("TrackValue is not valid.".to_string())),
+        }
+    }
+}
+
+//整数四舍五入后转换，超出范围时取最大或最小值，NaN 为 0
+macro_rules! impl_animate_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl AnimateValue for $ty {
+                const DISCRETE: bool = true;
+
+                fn from_track_value(
+                    value: &TrackValue,
+                    _asset_server: &AssetServer,
+                ) -> Result<Self, ReflectError> {
+                    match value {
+                        TrackValue::Number(number) => Ok(number.round() as $ty),
+                        _ => Err(ReflectError::Kind("TrackValue is not valid.".to_string())),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_animate_integer!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+impl AnimateValue for f32 {
+    fn from_track_value(
+        value: &TrackValue,
+        _asset_server: &AssetServer,
+    ) -> Result<Self, ReflectError> {
+        match value {
+            TrackValue::Number(number) => Ok(*number),
+            _ => Err(ReflectError::Kind("TrackValue is not valid.".to_string())),
+        }
+    }
+}
+
+impl AnimateValue for f64 {
+    fn from_track_value(
+        value: &TrackValue,
+        _asset_server: &AssetServer,
+    ) -> Result<Self, ReflectError> {
+        match value {
+            TrackValue::Number(number) => Ok(*number as f64),
+            _ => Err(ReflectError::Kind("TrackValue is not valid.".to_string())),
+        }
+    }
+}
+
+impl AnimateValue for Vec2 {
+    fn from_track_value(
+        value: &TrackValue,
+        _asset_server: &AssetServer,
+    ) -> Result<Self, ReflectError> {
+        match value {
+            TrackValue::Vec2(value) => Ok(*value),
+            _ => Err(ReflectError::Kind("TrackValue is not valid.".to_string())),
+        }
+    }
+}
+
+impl AnimateValue for Vec3 {
+    fn from_track_value(
+        value: &TrackValue,
+        _asset_server: &AssetServer,
+    ) -> Result<Self, ReflectError> {
+        match value {
+            TrackValue::Vec3(value) => Ok(*value),
+            _ => Err(ReflectError::Kind("TrackValue is not valid.".to_string())),
+        }
+    }
+}
+
+impl AnimateValue for Vec4 {
+    fn from_track_value(
+        value: &TrackValue,
+        _asset_server: &AssetServer,
+    ) -> Result<Self, ReflectError> {
+        match value {
+            TrackValue::Vec4(value) => Ok(*value),
+            _ => Err(ReflectError::Kind("TrackValue is not valid.".to_string())),
+        }
+    }
+}
+
+impl AnimateValue for Quat {
+    fn from_track_value(
+        value: &TrackValue,
+        _asset_server: &AssetServer,
+    ) -> Result<Self, ReflectError> {
+        match value {
+            TrackValue::Quat(value) => Ok(*value),
+            _ => Err(ReflectError::Kind("TrackValue is not valid.".to_string())),
+        }
+    }
+}
+
+impl AnimateValue for Color {
+    fn from_track_value(
+        value: &TrackValue,
+        _asset_server: &AssetServer,
+    ) -> Result<Self, ReflectError> {
+        match value {
+            TrackValue::Color(value) => Ok(*value),
+            _ => Err(ReflectError::Kind("TrackValue is not valid.".to_string())),
+        }
+    }
+}
+
+//数值为像素，其他单位使用 TrackValue::Reflect
+impl AnimateValue for Val {
+    fn from_track_value(
+        value: &TrackValue,
+        _asset_server: &AssetServer,
+    ) -> Result<Self, ReflectError> {
+        match value {
+            TrackValue::Number(number) => Ok(Val::Px(*number)),
+            _ => Err(ReflectError::Kind("TrackValue is not valid.".to_string())),
+        }
+    }
+}
+
+//四舍五入后 0 为 Hidden，1 为 Visible，其他为 Inherited
+impl AnimateValue for Visibility {
+    const DISCRETE: bool = true;
+
+    fn from_track_value(
+        value: &TrackValue,
+        _asset_server: &AssetServer,
+    ) -> Result<Self, ReflectError> {
+        match value {
+            TrackValue::Number(number) => Ok(match number.round() as i32 {
+                0 => Visibility::Hidden,
+                1 => Visibility::Visible,
+                _ => Visibility::Inherited,
+            }),
+            _ => Err(ReflectError::Kind("TrackValue is not valid.".to_string())),
+        }
+    }
+}
+
+impl<A: Asset> AnimateValue for Handle<A> {
+    fn load_dependency(asset: &AssetPath, load_context: &mut LoadContext) -> Option<UntypedHandle> {
+        //没有路径的资源只能在运行时通过 handle 使用
+        if asset.path.is_empty() {
+            return None;
+        }
+
+        Some(load_context.load::<A>(asset.path.to_string()).untyped())
+    }
+
+    fn from_track_value(
+        value: &TrackValue,
+        asset_server: &AssetServer,
+    ) -> Result<Self, ReflectError> {
+        match value {
+            TrackValue::Asset(asset) => match asset.handle() {
+                Some(handle) => handle
+                    .clone()
+                    .try_typed::<A>()
+                    .map_err(|e| ReflectError::Kind(e.to_string())),
+                //没有通过 EntityAnimationsLoader 加载的动画
+                None => {
+                    if asset.type_path != ShortTypePath::from_type_path::<Self>() {
+                        Err(ReflectError::Kind("asset type mismatch.".to_string()))
+                    } else if asset.path.is_empty() {
+                        Err(ReflectError::Kind("asset has no path.".to_string()))
+                    } else {
+                        Ok(asset_server.load(asset.path.to_string()))
+                    }
+                }
+            },
+            _ => Err(ReflectError::Kind("TrackValue is not valid.".to_string())),
+        }
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_number_values() {
+        use super::{AnimateValue, AnimateValueFns};
+        use crate::value::TrackValue;
+        use bevy::prelude::*;
+
+        assert!(AnimateValueFns::new::<usize>().discrete);
+        assert!(AnimateValueFns::new::<Visibility>().discrete);
+        assert!(!AnimateValueFns::new::<f32>().discrete);
+
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()));
+        let asset_server = app.world().resource::<AssetServer>();
+
+        let number = |value| TrackValue::Number(value);
+
+        assert_eq!(u8::from_track_value(&number(2.5), asset_server).unwrap(), 3);
+        assert_eq!(
+            u8::from_track_value(&number(300.0), asset_server).unwrap(),
+            255
+        );
+        assert_eq!(
+            u32::from_track_value(&number(-3.0), asset_server).unwrap(),
+            0
+        );
+        assert_eq!(
+            usize::from_track_value(&number(-1.0), asset_server).unwrap(),
+            0
+        );
+        assert_eq!(
+            usize::from_track_value(&number(1.6), asset_server).unwrap(),
+            2
+        );
+        assert_eq!(
+            i8::from_track_value(&number(-200.0), asset_server).unwrap(),
+            -128
+        );
+        assert_eq!(
+            i32::from_track_value(&number(-1.4), asset_server).unwrap(),
+            -1
+        );
+        assert_eq!(
+            i64::from_track_value(&number(f32::NAN), asset_server).unwrap(),
+            0
+        );
+        assert_eq!(
+            i128::from_track_value(&number(-2.5), asset_server).unwrap(),
+            -3
+        );
+        assert_eq!(
+            u128::from_track_value(&number(-2.5), asset_server).unwrap(),
+            0
+        );
+        assert_eq!(
+            f32::from_track_value(&number(0.25), asset_server).unwrap(),
+            0.25
+        );
+        assert_eq!(
+            f64::from_track_value(&number(0.25), asset_server).unwrap(),
+            0.25
+        );
+        assert!(bool::from_track_value(&number(1.0), asset_server).unwrap());
+        assert!(!bool::from_track_value(&number(0.0), asset_server).unwrap());
+
+        assert!(i32::from_track_value(&TrackValue::Vec2(Vec2::ONE), asset_server).is_err());
+    }
+
+    #[test]
+    fn test_math_values() {
+        use super::AnimateValue;
+        use crate::value::TrackValue;
+        use bevy::prelude::*;
+
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()));
+        let asset_server = app.world().resource::<AssetServer>();
+
+        let vec2 = TrackValue::Vec2(Vec2::new(1.0, 2.0));
+        let vec3 = TrackValue::Vec3(Vec3::ONE);
+        let quat = TrackValue::Quat(Quat::from_rotation_z(1.0));
+        let color = TrackValue::Color(Color::WHITE);
+        let vec4 = TrackValue::Vec4(Vec4::new(1.0, 2.0, 3.0, 4.0));
+
+        assert_eq!(
+            Vec2::from_track_value(&vec2, asset_server).unwrap(),
+            Vec2::new(1.0, 2.0)
+        );
+        assert_eq!(
+            Vec3::from_track_value(&vec3, asset_server).unwrap(),
+            Vec3::ONE
+        );
+        assert_eq!(
+            Quat::from_track_value(&quat, asset_server).unwrap(),
+            Quat::from_rotation_z(1.0)
+        );
+        assert_eq!(
+            Color::from_track_value(&color, asset_server).unwrap(),
+            Color::WHITE
+        );
+        assert_eq!(
+            Vec4::from_track_value(&vec4, asset_server).unwrap(),
+            Vec4::new(1.0, 2.0, 3.0, 4.0)
+        );
+
+        assert!(Vec3::from_track_value(&vec2, asset_server).is_err());
+        assert!(Quat::from_track_value(&TrackValue::Vec4(Vec4::ONE), asset_server).is_err());
+        assert!(Color::from_track_value(&TrackValue::Number(1.0), asset_server).is_err());
+        assert!(Vec4::from_track_value(&vec3, asset_server).is_err());
+    }
+
+    #[test]
+    fn test_handle_values() {
+        use super::AnimateValue;
+        use crate::core::ShortTypePath;
+        use crate::value::{AssetPath, TrackValue};
+        use bevy::prelude::*;
+
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()))
+            .init_asset::<Image>()
+            .init_asset::<TextureAtlasLayout>();
+
+        //运行时添加的图集布局没有路径，通过 handle 引用
+        let layout = app
+            .world_mut()
+            .resource_mut::<Assets<TextureAtlasLayout>>()
+            .add(TextureAtlasLayout::from_grid(
+                UVec2::splat(16),
+                2,
+                2,
+                None,
+                None,
+            ));
+        let asset_server = app.world().resource::<AssetServer>();
+
+        let value = TrackValue::Asset(AssetPath::from_handle(&layout));
+        assert_eq!(
+            Handle::<TextureAtlasLayout>::from_track_value(&value, asset_server).unwrap(),
+            layout
+        );
+        assert!(Handle::<Image>::from_track_value(&value, asset_server).is_err());
+
+        //没有通过 EntityAnimationsLoader 加载时按路径加载
+        let type_path = ShortTypePath::from_type_path::<Handle<TextureAtlasLayout>>();
+        let value = TrackValue::Asset(AssetPath::new("sheet.atlas#layout", type_path.clone()));
+        let handle = Handle::<TextureAtlasLayout>::from_track_value(&value, asset_server).unwrap();
+        assert_eq!(handle.path().unwrap().to_string(), "sheet.atlas#layout");
+
+        assert!(Handle::<Image>::from_track_value(&value, asset_server).is_err());
+        assert!(Handle::<TextureAtlasLayout>::from_track_value(
+            &TrackValue::Asset(AssetPath::new("", type_path)),
+            asset_server
+        )
+        .is_err());
+        assert!(Handle::<TextureAtlasLayout>::from_track_value(
+            &TrackValue::Number(1.0),
+            asset_server
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_ui_values() {
+        use super::AnimateValue;
+        use crate::value::TrackValue;
+        use bevy::prelude::*;
+
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()));
+        let asset_server = app.world().resource::<AssetServer>();
+
+        assert_eq!(
+            Val::from_track_value(&TrackValue::Number(10.0), asset_server).unwrap(),
+            Val::Px(10.0)
+        );
+
+        let visibility =
+            |value| Visibility::from_track_value(&TrackValue::Number(value), asset_server).unwrap();
+        assert_eq!(visibility(0.0), Visibility::Hidden);
+        assert_eq!(visibility(0.8), Visibility::Visible);
+        assert_eq!(visibility(2.0), Visibility::Inherited);
+
+        assert!(Visibility::from_track_value(&TrackValue::Vec2(Vec2::ONE), asset_server).is_err());
+    }
+}
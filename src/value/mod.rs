@@ -1,94 +1,672 @@
-mod animate_value;
-
-pub use animate_value::*;
-
-use bevy::{
-    asset::AssetServer,
-    reflect::{Reflect, TypeRegistry},
-};
-use serde::{Deserialize, Serialize};
-use thiserror::Error;
-
-use crate::core::ShortTypePath;
-
-#[derive(Debug, Error)]
-pub enum ReflectError {
-    #[error("reflect error: {0}")]
-    Kind(String),
-}
-
-pub struct ReflectBoundValue {
-    pub path: String,
-    pub value: Box<dyn Reflect>,
-}
-
-impl Clone for ReflectBoundValue {
-    fn clone(&self) -> Self {
-        ReflectBoundValue {
-            path: self.path.clone(),
-            value: self.value.clone_value(),
-        }
-    }
-}
-
-///组件修改的字段路径和关键帧的数据类型
-#[derive(Clone, Serialize, Deserialize)]
-pub struct ValueBinding {
-    pub path: String,
-    pub value_type: ShortTypePath,
-}
-
-#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, PartialOrd)]
-pub struct AssetPath {
-    pub path: String,
-    pub type_path: ShortTypePath,
-}
-
-///原始的关键帧数据
-#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, PartialOrd)]
-pub enum TrackValue {
-    Number(f32),
-    Asset(AssetPath),
-}
-
-impl TrackValue {
-    pub fn blend_with(&mut self, _other: &Self, _weight: f32) {
-        todo!()
-    }
-}
-
-///用来修改组件的关键帧数据抽象
-#[derive(Clone)]
-pub struct BoundValue {
-    pub binding: ValueBinding,
-    pub value: TrackValue,
-}
-
-impl BoundValue {
-    ///根据weight 混合
-    pub fn blend_with(&mut self, other: &Self, weight: f32) {
-        assert_eq!(self.binding.path, other.binding.path);
-        self.value.blend_with(&other.value, weight);
-    }
-
-    pub fn get_relect_value(
-        &self,
-        registry: &TypeRegistry,
-        asset_server: &AssetServer,
-    ) -> Result<Box<dyn Reflect>, ReflectError> {
-        let registraion = registry
-            .get_with_short_type_path(&self.binding.value_type)
-            .ok_or(ReflectError::Kind(format!(
-                "{:?} not register type",
-                self.binding.value_type
-            )))?;
-        if let Some(fns) = registraion.data::<AnimateValueFns>() {
-            (fns.reflect)(&self.value, asset_server)
-        } else {
-            Err(ReflectError::Kind(format!(
-                "{:?} not register animate value",
-                self.binding.value_type
-            )))
-        }
-    }
-}
+mod animate_value;
+
+pub use animate_value::*;
+
+use std::sync::Arc;
+
+use bevy::{
+    asset::{Asset, Handle, UntypedHandle},
+    color::{Color, Mix},
+    math::{Quat, Vec2, Vec3, Vec4},
+    reflect::{
+        serde::{ReflectSerializer, TypedReflectDeserializer},
+        Reflect, ReflectFromReflect, TypeRegistry,
+    },
+};
+use serde::{de::DeserializeSeed, Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::core::ShortTypePath;
+
+#[derive(Debug, Error)]
+pub enum ReflectError {
+    #[error("reflect error: {0}")]
+    Kind(String),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum BlendError {
+    #[error("can not blend {0} with {1}.")]
+    ValueMismatch(&'static str, &'static str),
+    #[error("can not blend asset type {0:?} with {1:?}.")]
+    AssetTypeMismatch(ShortTypePath, ShortTypePath),
+    #[error("can not blend binding {0} with {1}.")]
+    BindingMismatch(String, String),
+    #[error("{0} can not be added.")]
+    NotAdditive(&'static str),
+    #[error("can not blend reflect type {0:?} with {1:?}.")]
+    ReflectTypeMismatch(Option<String>, Option<String>),
+}
+
+///资源类型的关键帧无法插值，权重达到该阈值时切换为目标值
+pub const ASSET_BLEND_THRESHOLD: f32 = 0.5;
+
+///组件修改的字段路径和关键帧的数据类型
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ValueBinding {
+    pub path: String,
+    pub value_type: ShortTypePath,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AssetPath {
+    //采样时会复制关键帧的值，路径共享同一份数据
+    pub path: Arc<str>,
+    pub type_path: ShortTypePath,
+    //加载 EntityAnimations 时作为依赖加载的强引用
+    #[serde(skip)]
+    handle: Option<UntypedHandle>,
+}
+
+impl AssetPath {
+    pub fn new(path: &str, type_path: ShortTypePath) -> Self {
+        Self {
+            path: path.into(),
+            type_path,
+            handle: None,
+        }
+    }
+
+    ///从已有的 handle 创建，运行时添加的资源没有路径，只能在运行时使用
+    pub fn from_handle<A: Asset>(handle: &Handle<A>) -> Self {
+        Self {
+            path: handle
+                .path()
+                .map(|path| path.to_string())
+                .unwrap_or_default()
+                .into(),
+            type_path: ShortTypePath::from_type_path::<Handle<A>>(),
+            handle: Some(handle.clone().untyped()),
+        }
+    }
+
+    pub fn handle(&self) -> Option<&UntypedHandle> {
+        self.handle.as_ref()
+    }
+
+    pub fn set_handle(&mut self, handle: UntypedHandle) {
+        self.handle = Some(handle);
+    }
+}
+
+impl PartialEq for AssetPath {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.type_path == other.type_path
+    }
+}
+
+///任意反射类型的关键帧，保存 ReflectSerializer 的输出，加载时通过类型注册解析
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct ReflectValue {
+    data: serde_json::Value,
+    #[serde(skip)]
+    value: Option<Arc<dyn Reflect>>,
+}
+
+impl ReflectValue {
+    pub fn new(value: &dyn Reflect, registry: &TypeRegistry) -> Result<Self, ReflectError> {
+        let data = serde_json::to_value(ReflectSerializer::new(value, registry))
+            .map_err(|e| ReflectError::Kind(e.to_string()))?;
+
+        Ok(Self {
+            data,
+            value: Some(Arc::from(value.clone_value())),
+        })
+    }
+
+    ///值的完整类型路径
+    pub fn type_path(&self) -> Option<&str> {
+        self.data
+            .as_object()
+            .and_then(|data| data.keys().next())
+            .map(|type_path| type_path.as_str())
+    }
+
+    ///解析后的值，没有调用 resolve 时为 None
+    pub fn value(&self) -> Option<&dyn Reflect> {
+        self.value.as_deref()
+    }
+
+    ///通过类型注册解析保存的数据，类型注册了 ReflectFromReflect 时转换为具体类型
+    pub fn resolve(&mut self, registry: &TypeRegistry) -> Result<(), ReflectError> {
+        let Some((type_path, data)) = self.data.as_object().and_then(|data| data.iter().next())
+        else {
+            return Err(ReflectError::Kind("reflect value is empty.".to_string()));
+        };
+
+        let Some(registration) = registry.get_with_type_path(type_path) else {
+            return Err(ReflectError::Kind(format!(
+                "{} not register_type.",
+                type_path
+            )));
+        };
+
+        let value = TypedReflectDeserializer::new(registration, registry)
+            .deserialize(data)
+            .map_err(|e| ReflectError::Kind(e.to_string()))?;
+
+        let value = registration
+            .data::<ReflectFromReflect>()
+            .and_then(|from_reflect| from_reflect.from_reflect(&*value))
+            .unwrap_or(value);
+
+        self.value = Some(Arc::from(value));
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for ReflectValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ReflectValue").field(&self.data).finish()
+    }
+}
+
+impl PartialEq for ReflectValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+    }
+}
+
+///原始的关键帧数据
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum TrackValue {
+    Number(f32),
+    Asset(AssetPath),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Vec4(Vec4),
+    //使用球面插值混合
+    Quat(Quat),
+    //在 self 所在的颜色空间中混合，例如 Color::Oklaba 会在 Oklab 空间中混合
+    Color(Color),
+    //任意反射类型，无法插值，按阈值切换
+    Reflect(ReflectValue),
+}
+
+impl TrackValue {
+    ///连续类型之间的插值，类型不同或无法插值时返回 None
+    fn lerp(&self, other: &Self, t: f32) -> Option<Self> {
+        let value = match (self, other) {
+            (TrackValue::Number(a), TrackValue::Number(b)) => TrackValue::Number(a + (b - a) * t),
+            (TrackValue::Vec2(a), TrackValue::Vec2(b)) => TrackValue::Vec2(a.lerp(*b, t)),
+            (TrackValue::Vec3(a), TrackValue::Vec3(b)) => TrackValue::Vec3(a.lerp(*b, t)),
+            (TrackValue::Vec4(a), TrackValue::Vec4(b)) => TrackValue::Vec4(a.lerp(*b, t)),
+            (TrackValue::Quat(a), TrackValue::Quat(b)) => TrackValue::Quat(a.slerp(*b, t)),
+            (TrackValue::Color(a), TrackValue::Color(b)) => TrackValue::Color(a.mix(b, t)),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    ///在 self 和 other 之间插值，t 为 0 时返回 self，无法插值的类型保持 self
+    pub fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self.lerp(other, t).unwrap_or_else(|| self.clone())
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TrackValue::Number(_) => "Number",
+            TrackValue::Asset(_) => "Asset",
+            TrackValue::Vec2(_) => "Vec2",
+            TrackValue::Vec3(_) => "Vec3",
+            TrackValue::Vec4(_) => "Vec4",
+            TrackValue::Quat(_) => "Quat",
+            TrackValue::Color(_) => "Color",
+            TrackValue::Reflect(_) => "Reflect",
+        }
+    }
+
+    ///根据weight 混合，weight 为 0 时保持 self，为 1 时等于 other
+    pub fn blend_with(&mut self, other: &Self, weight: f32) -> Result<(), BlendError> {
+        self.blend_with_threshold(other, weight, ASSET_BLEND_THRESHOLD)
+    }
+
+    ///根据weight 混合，无法插值的资源类型在 weight 达到 threshold 时切换为 other
+    pub fn blend_with_threshold(
+        &mut self,
+        other: &Self,
+        weight: f32,
+        threshold: f32,
+    ) -> Result<(), BlendError> {
+        match (&mut *self, other) {
+            (TrackValue::Asset(a), TrackValue::Asset(b)) => {
+                if a.type_path != b.type_path {
+                    return Err(BlendError::AssetTypeMismatch(
+                        a.type_path.clone(),
+                        b.type_path.clone(),
+                    ));
+                }
+
+                if weight >= threshold && a != b {
+                    *a = b.clone();
+                }
+            }
+            (TrackValue::Reflect(a), TrackValue::Reflect(b)) => {
+                if a.type_path() != b.type_path() {
+                    return Err(BlendError::ReflectTypeMismatch(
+                        a.type_path().map(str::to_string),
+                        b.type_path().map(str::to_string),
+                    ));
+                }
+
+                if weight >= threshold && a != b {
+                    *a = b.clone();
+                }
+            }
+            (a, b) => match a.lerp(b, weight) {
+                Some(value) => *a = value,
+                None => return Err(BlendError::ValueMismatch(a.kind(), b.kind())),
+            },
+        }
+
+        Ok(())
+    }
+}
+
+impl TrackValue {
+    ///相对 reference 的差值，和 add_weighted 互逆，四元数为 self * reference⁻¹
+    pub fn difference(&self, reference: &Self) -> Result<Self, BlendError> {
+        let value = match (self, reference) {
+            (TrackValue::Number(a), TrackValue::Number(b)) => TrackValue::Number(a - b),
+            (TrackValue::Vec2(a), TrackValue::Vec2(b)) => TrackValue::Vec2(*a - *b),
+            (TrackValue::Vec3(a), TrackValue::Vec3(b)) => TrackValue::Vec3(*a - *b),
+            (TrackValue::Vec4(a), TrackValue::Vec4(b)) => TrackValue::Vec4(*a - *b),
+            (TrackValue::Quat(a), TrackValue::Quat(b)) => {
+                TrackValue::Quat((*a * b.inverse()).normalize())
+            }
+            (TrackValue::Color(a), TrackValue::Color(b)) => {
+                TrackValue::Color(Color::LinearRgba(a.to_linear() - b.to_linear()))
+            }
+            (TrackValue::Asset(_), TrackValue::Asset(_)) => {
+                return Err(BlendError::NotAdditive("Asset"));
+            }
+            (TrackValue::Reflect(_), TrackValue::Reflect(_)) => {
+                return Err(BlendError::NotAdditive("Reflect"));
+            }
+            (a, b) => return Err(BlendError::ValueMismatch(a.kind(), b.kind())),
+        };
+
+        Ok(value)
+    }
+
+    ///叠加 other * weight，用于叠加动画，四元数按 weight 叠加旋转，颜色在线性空间中相加
+    pub fn add_weighted(&mut self, other: &Self, weight: f32) -> Result<(), BlendError> {
+        match (&mut *self, other) {
+            (TrackValue::Number(a), TrackValue::Number(b)) => *a += b * weight,
+            (TrackValue::Vec2(a), TrackValue::Vec2(b)) => *a += *b * weight,
+            (TrackValue::Vec3(a), TrackValue::Vec3(b)) => *a += *b * weight,
+            (TrackValue::Vec4(a), TrackValue::Vec4(b)) => *a += *b * weight,
+            (TrackValue::Quat(a), TrackValue::Quat(b)) => {
+                *a = (Quat::IDENTITY.slerp(*b, weight) * *a).normalize();
+            }
+            (TrackValue::Color(a), TrackValue::Color(b)) => {
+                *a = Color::LinearRgba(a.to_linear() + b.to_linear() * weight);
+            }
+            (TrackValue::Asset(_), TrackValue::Asset(_)) => {
+                return Err(BlendError::NotAdditive("Asset"));
+            }
+            (TrackValue::Reflect(_), TrackValue::Reflect(_)) => {
+                return Err(BlendError::NotAdditive("Reflect"));
+            }
+            (a, b) => return Err(BlendError::ValueMismatch(a.kind(), b.kind())),
+        }
+
+        Ok(())
+    }
+}
+
+///用来修改组件的关键帧数据抽象
+#[derive(Clone)]
+pub struct BoundValue {
+    pub binding: ValueBinding,
+    pub value: TrackValue,
+    //字段类型无法插值，混合时切换，采样时由编译后的绑定设置
+    pub discrete: bool,
+}
+
+impl BoundValue {
+    ///根据weight 混合
+    pub fn blend_with(&mut self, other: &Self, weight: f32) -> Result<(), BlendError> {
+        self.blend_with_threshold(other, weight, ASSET_BLEND_THRESHOLD)
+    }
+
+    ///根据weight 混合，无法插值的值在 weight 达到 threshold 时切换为 other
+    pub fn blend_with_threshold(
+        &mut self,
+        other: &Self,
+        weight: f32,
+        threshold: f32,
+    ) -> Result<(), BlendError> {
+        if self.binding.path != other.binding.path {
+            return Err(BlendError::BindingMismatch(
+                self.binding.path.clone(),
+                other.binding.path.clone(),
+            ));
+        }
+
+        if self.discrete || other.discrete {
+            if weight >= threshold && self.value != other.value {
+                self.value.clone_from(&other.value);
+            }
+
+            return Ok(());
+        }
+
+        self.value
+            .blend_with_threshold(&other.value, weight, threshold)
+    }
+
+    ///结果与 from.blend_with_threshold(self) 相同，但写入 self，不复制 from 的绑定
+    ///
+    ///无法插值的值在 weight 达到 threshold 前保持 from 的值
+    pub fn blend_from(&mut self, from: &Self, weight: f32, threshold: f32) {
+        let interpolable = !(self.discrete || from.discrete)
+            && self.value.kind() == from.value.kind()
+            && !matches!(self.value, TrackValue::Asset(_) | TrackValue::Reflect(_));
+
+        if !interpolable {
+            if weight < threshold && self.value != from.value {
+                self.value.clone_from(&from.value);
+            }
+            return;
+        }
+
+        //可以插值的值没有堆上的数据，复制的开销很小
+        let mut blended = from.value.clone();
+        if blended
+            .blend_with_threshold(&self.value, weight, threshold)
+            .is_ok()
+        {
+            self.value = blended;
+        }
+    }
+
+    ///叠加 other * weight
+    pub fn add_weighted(&mut self, other: &Self, weight: f32) -> Result<(), BlendError> {
+        if self.binding.path != other.binding.path {
+            return Err(BlendError::BindingMismatch(
+                self.binding.path.clone(),
+                other.binding.path.clone(),
+            ));
+        }
+
+        self.value.add_weighted(&other.value, weight)
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_blend_number() {
+        use super::TrackValue;
+
+        let mut value = TrackValue::Number(1.0);
+        value.blend_with(&TrackValue::Number(3.0), 0.25).unwrap();
+
+        assert_eq!(value, TrackValue::Number(1.5));
+    }
+
+    #[test]
+    fn test_blend_discrete() {
+        use super::{BoundValue, TrackValue, ValueBinding};
+        use crate::core::ShortTypePath;
+
+        let bound = |value| BoundValue {
+            binding: ValueBinding {
+                path: ".index".to_owned(),
+                value_type: ShortTypePath::from_type_path::<usize>(),
+            },
+            value: TrackValue::Number(value),
+            discrete: true,
+        };
+
+        let mut value = bound(1.0);
+        value.blend_with(&bound(5.0), 0.4).unwrap();
+        assert_eq!(value.value, TrackValue::Number(1.0));
+
+        value.blend_with(&bound(5.0), 0.6).unwrap();
+        assert_eq!(value.value, TrackValue::Number(5.0));
+    }
+
+    #[test]
+    fn test_blend_from() {
+        use super::{BoundValue, TrackValue, ValueBinding};
+        use crate::core::ShortTypePath;
+
+        let bound = |value, discrete| BoundValue {
+            binding: ValueBinding {
+                path: ".a".to_owned(),
+                value_type: ShortTypePath::from_type_path::<f32>(),
+            },
+            value: TrackValue::Number(value),
+            discrete,
+        };
+
+        //与 from.blend_with(self) 的结果相同
+        let mut value = bound(4.0, false);
+        value.blend_from(&bound(0.0, false), 0.25, 0.5);
+        assert_eq!(value.value, TrackValue::Number(1.0));
+
+        let mut value = bound(4.0, true);
+        value.blend_from(&bound(0.0, true), 0.25, 0.5);
+        assert_eq!(value.value, TrackValue::Number(0.0));
+
+        let mut value = bound(4.0, true);
+        value.blend_from(&bound(0.0, true), 0.5, 0.5);
+        assert_eq!(value.value, TrackValue::Number(4.0));
+    }
+
+    #[test]
+    fn test_blend_asset() {
+        use super::{AssetPath, TrackValue};
+        use crate::core::ShortTypePath;
+
+        let a = TrackValue::Asset(AssetPath::new(
+            "a.png",
+            ShortTypePath::from_type_path::<bool>(),
+        ));
+        let b = TrackValue::Asset(AssetPath::new(
+            "b.png",
+            ShortTypePath::from_type_path::<bool>(),
+        ));
+
+        let mut value = a.clone();
+        value.blend_with(&b, 0.4).unwrap();
+        assert_eq!(value, a);
+
+        value.blend_with(&b, 0.5).unwrap();
+        assert_eq!(value, b);
+    }
+
+    #[test]
+    fn test_blend_mismatch() {
+        use super::{AssetPath, BlendError, TrackValue};
+        use crate::core::ShortTypePath;
+
+        let mut value = TrackValue::Number(1.0);
+        let asset = TrackValue::Asset(AssetPath::new(
+            "a.png",
+            ShortTypePath::from_type_path::<bool>(),
+        ));
+
+        assert_eq!(
+            value.blend_with(&asset, 0.5),
+            Err(BlendError::ValueMismatch("Number", "Asset"))
+        );
+        assert_eq!(value, TrackValue::Number(1.0));
+    }
+
+    #[test]
+    fn test_blend_vector() {
+        use super::TrackValue;
+        use bevy::math::{Vec2, Vec3};
+
+        let mut value = TrackValue::Vec2(Vec2::ZERO);
+        value
+            .blend_with(&TrackValue::Vec2(Vec2::new(2.0, 4.0)), 0.5)
+            .unwrap();
+        assert_eq!(value, TrackValue::Vec2(Vec2::new(1.0, 2.0)));
+
+        let mut value = TrackValue::Vec3(Vec3::ZERO);
+        assert!(value.blend_with(&TrackValue::Vec2(Vec2::ONE), 0.5).is_err());
+    }
+
+    #[test]
+    fn test_blend_quat() {
+        use super::TrackValue;
+        use bevy::math::Quat;
+        use std::f32::consts::PI;
+
+        let mut value = TrackValue::Quat(Quat::IDENTITY);
+        value
+            .blend_with(&TrackValue::Quat(Quat::from_rotation_z(PI / 2.0)), 0.5)
+            .unwrap();
+
+        let TrackValue::Quat(quat) = value else {
+            panic!("value is not quat");
+        };
+        assert!(quat.abs_diff_eq(Quat::from_rotation_z(PI / 4.0), 1e-5));
+        assert!(quat.is_normalized());
+    }
+
+    #[test]
+    fn test_blend_color() {
+        use super::TrackValue;
+        use bevy::color::{Color, LinearRgba, Srgba};
+
+        //在 sRGB 空间中混合
+        let mut value = TrackValue::Color(Color::srgb(0.0, 0.0, 0.0));
+        value
+            .blend_with(&TrackValue::Color(Color::srgb(1.0, 1.0, 1.0)), 0.5)
+            .unwrap();
+        assert_eq!(
+            value,
+            TrackValue::Color(Color::Srgba(Srgba::rgb(0.5, 0.5, 0.5)))
+        );
+
+        //在线性空间中混合
+        let mut value = TrackValue::Color(Color::linear_rgb(0.0, 0.0, 0.0));
+        value
+            .blend_with(&TrackValue::Color(Color::linear_rgb(1.0, 1.0, 1.0)), 0.5)
+            .unwrap();
+        assert_eq!(
+            value,
+            TrackValue::Color(Color::LinearRgba(LinearRgba::rgb(0.5, 0.5, 0.5)))
+        );
+    }
+
+    #[test]
+    fn test_add_weighted() {
+        use super::{AssetPath, BlendError, TrackValue};
+        use crate::core::ShortTypePath;
+        use bevy::math::{Quat, Vec3};
+        use std::f32::consts::PI;
+
+        let mut value = TrackValue::Number(1.0);
+        value.add_weighted(&TrackValue::Number(2.0), 0.5).unwrap();
+        assert_eq!(value, TrackValue::Number(2.0));
+
+        let mut value = TrackValue::Vec3(Vec3::ONE);
+        value.add_weighted(&TrackValue::Vec3(Vec3::X), 1.0).unwrap();
+        assert_eq!(value, TrackValue::Vec3(Vec3::new(2.0, 1.0, 1.0)));
+
+        let mut value = TrackValue::Quat(Quat::from_rotation_z(PI / 4.0));
+        value
+            .add_weighted(&TrackValue::Quat(Quat::from_rotation_z(PI / 2.0)), 0.5)
+            .unwrap();
+        let TrackValue::Quat(quat) = value else {
+            panic!("value is not quat");
+        };
+        assert!(quat.abs_diff_eq(Quat::from_rotation_z(PI / 2.0), 1e-5));
+
+        let asset = TrackValue::Asset(AssetPath::new(
+            "a.png",
+            ShortTypePath::from_type_path::<bool>(),
+        ));
+        let mut value = asset.clone();
+        assert_eq!(
+            value.add_weighted(&asset, 1.0),
+            Err(BlendError::NotAdditive("Asset"))
+        );
+    }
+
+    #[test]
+    fn test_difference() {
+        use super::TrackValue;
+        use bevy::math::{Quat, Vec2};
+
+        let value = TrackValue::Vec2(Vec2::new(3.0, 1.0));
+        let reference = TrackValue::Vec2(Vec2::ONE);
+        let mut delta = value.difference(&reference).unwrap();
+        assert_eq!(delta, TrackValue::Vec2(Vec2::new(2.0, 0.0)));
+
+        delta.add_weighted(&reference, 1.0).unwrap();
+        assert_eq!(delta, value);
+
+        let value = Quat::from_rotation_y(1.0);
+        let reference = Quat::from_rotation_y(0.25);
+        let delta = TrackValue::Quat(value)
+            .difference(&TrackValue::Quat(reference))
+            .unwrap();
+        let mut result = TrackValue::Quat(reference);
+        result.add_weighted(&delta, 1.0).unwrap();
+        let TrackValue::Quat(result) = result else {
+            panic!("value is not quat");
+        };
+        assert!(result.abs_diff_eq(value, 1e-5));
+    }
+
+    #[test]
+    fn test_reflect_value() {
+        use super::{AnimateValueFns, BlendError, ReflectValue, TrackValue};
+        use bevy::{prelude::*, reflect::TypeRegistry};
+
+        #[derive(Reflect, Debug, PartialEq)]
+        enum Facing {
+            Left,
+            Right(f32),
+        }
+
+        let mut registry = TypeRegistry::default();
+        registry.register::<Facing>();
+
+        let value = TrackValue::Reflect(ReflectValue::new(&Facing::Right(1.0), &registry).unwrap());
+        let json = serde_json::to_string(&value).unwrap();
+        assert!(json.contains("Right"));
+
+        let TrackValue::Reflect(mut loaded) = serde_json::from_str::<TrackValue>(&json).unwrap()
+        else {
+            panic!("not a reflect value.");
+        };
+        assert!(loaded.value().is_none());
+
+        loaded.resolve(&registry).unwrap();
+        assert_eq!(
+            loaded.value().unwrap().downcast_ref::<Facing>(),
+            Some(&Facing::Right(1.0))
+        );
+
+        //无法插值，按阈值切换
+        let left = TrackValue::Reflect(ReflectValue::new(&Facing::Left, &registry).unwrap());
+        let mut blended = left.clone();
+        blended.blend_with(&value, 0.4).unwrap();
+        assert_eq!(blended, left);
+        blended.blend_with(&value, 0.6).unwrap();
+        assert_eq!(blended, value);
+        assert_eq!(
+            blended.clone().add_weighted(&left, 1.0),
+            Err(BlendError::NotAdditive("Reflect"))
+        );
+
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()));
+        let asset_server = app.world().resource::<AssetServer>();
+
+        let mut field = Facing::Left;
+        (AnimateValueFns::reflect_only().apply)(&value, &mut field, asset_server).unwrap();
+        assert_eq!(field, Facing::Right(1.0));
+    }
+}